@@ -1,11 +1,17 @@
-//! This library implements the derivation from GrrVertex.  
+//! This library implements the derivation from GrrVertex and
+//! TexelType.
 //!
-//! Deriving this trait allows one to automatically create attributes
+//! Deriving GrrVertex allows one to automatically create attributes
 //! descriptions to pass on to GrrDevice::create_vertex_array, based
 //! on the fields defined in the structure.
 //!
 //! The static `attribs` method provides to these attributes, based on
 //! a binding index and initial location index.
+//!
+//! Deriving TexelType on a `#[repr(C)]` struct of one to four fields
+//! of the same component type lets arrays of the struct be uploaded
+//! to and read back from images directly. The fields are taken as
+//! the R, G, B and A components, in order.
 
 extern crate proc_macro;
 use proc_macro::TokenStream;
@@ -99,3 +105,111 @@ fn field_attrib(struct_ident: &syn::Ident, f: &syn::Field) -> proc_macro2::Token
 
     res
 }
+
+#[proc_macro_derive(TexelType)]
+pub fn texel_type_derive(input: TokenStream) -> TokenStream {
+    let ast: syn::DeriveInput = syn::parse(input).unwrap();
+    let ast_span = ast.span();
+
+    let data = if let syn::Data::Struct(d) = ast.data {
+        d
+    } else {
+        return quote_spanned! {
+            ast_span=>
+            compile_error!("TexelType can only be auto-derived on a struct.")
+        }
+        .into();
+    };
+
+    // The texel is reinterpreted as packed components, so the layout
+    // must be fixed, without extra alignment or packing.
+    if !has_plain_repr_c(&ast.attrs) {
+        return quote_spanned! {
+            ast_span=>
+            compile_error!("TexelType can only be auto-derived on a #[repr(C)] or #[repr(transparent)] struct, without align or packed.")
+        }
+        .into();
+    }
+
+    let base_format = match data.fields.len() {
+        1 => quote!(R),
+        2 => quote!(RG),
+        3 => quote!(RGB),
+        4 => quote!(RGBA),
+        _ => {
+            return quote_spanned! {
+                ast_span=>
+                compile_error!("TexelType can only be auto-derived on a struct with one to four fields.")
+            }
+            .into();
+        }
+    };
+
+    let struct_ident = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let component_ty = &data.fields.iter().next().unwrap().ty;
+    let num_fields = data.fields.len();
+    let field_access: Vec<_> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(x) => quote!(t.#x),
+            None => {
+                let index = syn::Index::from(i);
+                quote!(t.#index)
+            }
+        })
+        .collect();
+    let assert_fn_ident = format_ident!("_assert_same_component_{}", struct_ident);
+
+    // With every field of the same type, `repr(C)` leaves no padding;
+    // check it where the size is known without generics.
+    let assert_size = if ast.generics.params.is_empty() {
+        quote! {
+            const _: [(); #num_fields * std::mem::size_of::<#component_ty>()] =
+                [(); std::mem::size_of::<#struct_ident>()];
+        }
+    } else {
+        quote!()
+    };
+
+    (quote! {
+        // Assert that every field has the same component type.
+        #[allow(dead_code, non_snake_case)]
+        fn #assert_fn_ident #impl_generics (t: #struct_ident #ty_generics) -> [#component_ty; #num_fields] #where_clause {
+            [#(#field_access),*]
+        }
+
+        #assert_size
+
+        unsafe impl #impl_generics grr_util::image_format::TexelType for #struct_ident #ty_generics #where_clause {
+            type Component = #component_ty;
+            const base_format: grr::BaseFormat = grr::BaseFormat::#base_format;
+        }
+    })
+    .into()
+}
+
+/// Return true if the attributes hold `#[repr(C)]` or
+/// `#[repr(transparent)]`, and no other representation hints.
+fn has_plain_repr_c(attrs: &[syn::Attribute]) -> bool {
+    let mut is_c = false;
+    for attr in attrs.iter().filter(|a| a.path.is_ident("repr")) {
+        let list = match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list,
+            _ => return false,
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                syn::NestedMeta::Meta(syn::Meta::Path(p))
+                    if p.is_ident("C") || p.is_ident("transparent") =>
+                {
+                    is_c = true
+                }
+                _ => return false,
+            }
+        }
+    }
+    is_c
+}
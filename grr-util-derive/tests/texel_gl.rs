@@ -0,0 +1,72 @@
+//! Round trip of derived texels through an image, which needs an
+//! OpenGL context. glutin requires the event loop on the main
+//! thread, so this runs without the default test harness, and is
+//! skipped where no context can be created.
+use grr_util::image_manager::ImageRegion;
+use grr_util::{GrrBuilder, ImageManager};
+use grr_util_derive::TexelType;
+
+#[derive(TexelType, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct Rgba8 {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+}
+
+#[derive(TexelType, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct Rg32(f32, f32);
+
+fn round_trip_rgba8(device: &grr::Device) {
+    let data = ndarray::Array2::from_shape_fn((3, 5), |(y, x)| Rgba8 {
+        r: x as u8,
+        g: y as u8,
+        b: (x * y) as u8,
+        a: 255,
+    });
+
+    let mut images = ImageManager::new(device);
+    let image = images.create_image_from_texels(&data, 1, false).unwrap();
+    let read: ndarray::Array2<Rgba8> = images
+        .read_texels(image, &ImageRegion::default())
+        .unwrap();
+    assert_eq!(read, data);
+    images.clear();
+}
+
+fn round_trip_rg32(device: &grr::Device) {
+    let data = ndarray::Array2::from_shape_fn((4, 2), |(y, x)| Rg32(x as f32 * 0.5, -(y as f32)));
+
+    let mut images = ImageManager::new(device);
+    let image = images.create_image_from_texels(&data, 1, false).unwrap();
+    let read: ndarray::Array2<Rg32> = images
+        .read_texels(image, &ImageRegion::default())
+        .unwrap();
+    assert_eq!(read, data);
+    images.clear();
+}
+
+fn main() {
+    let headless = match std::panic::catch_unwind(|| GrrBuilder::new().build_headless()) {
+        Ok(Ok(h)) => h,
+        Ok(Err(e)) => {
+            eprintln!("skipping OpenGL tests: {}", e);
+            return;
+        }
+        Err(_) => {
+            eprintln!("skipping OpenGL tests: no event loop available");
+            return;
+        }
+    };
+
+    let tests: &[(&str, fn(&grr::Device))] = &[
+        ("round_trip_rgba8", round_trip_rgba8),
+        ("round_trip_rg32", round_trip_rg32),
+    ];
+    for (name, test) in tests {
+        test(&headless.device);
+        println!("test {} ... ok", name);
+    }
+}
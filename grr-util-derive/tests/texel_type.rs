@@ -0,0 +1,54 @@
+use grr_util::image_format::TexelType;
+use grr_util_derive::TexelType;
+
+#[derive(TexelType, Clone, Copy)]
+#[repr(C)]
+pub struct Rgba8 {
+    r: u8,
+    g: u8,
+    b: u8,
+    a: u8,
+}
+
+#[derive(TexelType, Clone, Copy)]
+#[repr(C)]
+pub struct Rg32(f32, f32);
+
+#[derive(TexelType, Clone, Copy)]
+#[repr(C)]
+pub struct Height {
+    h: u16,
+}
+
+#[test]
+pub fn test_derived_texels() {
+    assert_eq!(Rgba8::base_format, grr::BaseFormat::RGBA);
+    assert_eq!(Rgba8::format(), Some(grr::Format::R8G8B8A8_UNORM));
+
+    assert_eq!(Rg32::base_format, grr::BaseFormat::RG);
+    assert_eq!(Rg32::format(), Some(grr::Format::R32G32_SFLOAT));
+
+    assert_eq!(Height::base_format, grr::BaseFormat::R);
+    assert_eq!(Height::format(), Some(grr::Format::R16_UNORM));
+}
+
+#[test]
+pub fn test_builtin_texels() {
+    assert_eq!(<[u8; 4]>::format(), Some(grr::Format::R8G8B8A8_UNORM));
+    assert_eq!(
+        palette::Srgba::<u8>::format(),
+        Some(grr::Format::R8G8B8A8_SRGB)
+    );
+    assert_eq!(
+        palette::LinSrgba::<f32>::format(),
+        Some(grr::Format::R32G32B32A32_SFLOAT)
+    );
+    assert_eq!(
+        nalgebra::Vector4::<half::f16>::format(),
+        Some(grr::Format::R16G16B16A16_SFLOAT)
+    );
+    assert_eq!(
+        nalgebra::Vector2::<f32>::format(),
+        Some(grr::Format::R32G32_SFLOAT)
+    );
+}
@@ -0,0 +1,198 @@
+//! Application runner driving the event loop of a `GrrWindow`.
+//!
+//! `run` handles the glutin event loop, buffer swaps, resizing and
+//! imgui plumbing, and calls into an `App` in a fixed order each
+//! frame: events, `input`, `update`, fixed updates, `render`, then
+//! `ui`.
+use crate::debug;
+use crate::input::InputState;
+use crate::timing::{FixedTimestep, FrameLimiter};
+use crate::window::{set_full_viewport, Error, GrrImgui};
+use glutin::dpi::PhysicalSize;
+use glutin::event::{Event, WindowEvent};
+use glutin::event_loop::{ControlFlow, EventLoop};
+use glutin::platform::run_return::EventLoopExtRunReturn;
+use glutin::{PossiblyCurrent, WindowedContext};
+
+/// An application driven by `run`.
+///
+/// Every method but `render` has an empty default.
+pub trait App {
+    /// Called once, before the first frame.
+    fn init(&mut self, _device: &grr::Device, _window: &WindowedContext<PossiblyCurrent>) {}
+
+    /// Called once per frame, after the frame's events and before
+    /// `update`, with the input state of the frame.
+    fn input(&mut self, _input: &InputState) {}
+
+    /// Advance the application by `dt` seconds. Called once per
+    /// frame, after the frame's events.
+    fn update(&mut self, _dt: f32) {}
+
+    /// Advance the simulation by one fixed step of `dt` seconds.
+    /// Only called when `RunOptions::fixed_timestep` is set, zero or
+    /// more times per frame after `update`.
+    fn fixed_update(&mut self, _dt: f32) {}
+
+    /// Called before `render` with the fraction of a fixed step
+    /// elapsed since the last fixed update, for interpolating between
+    /// simulation states.
+    fn interpolate(&mut self, _alpha: f32) {}
+
+    /// Whether fixed updates are paused. Checked every frame.
+    fn paused(&self) -> bool {
+        false
+    }
+
+    /// Return `true` to run exactly one fixed update while paused.
+    /// Checked every frame.
+    fn take_single_step(&mut self) -> bool {
+        false
+    }
+
+    /// Draw the frame. The viewport and scissor cover the window.
+    fn render(&mut self, device: &grr::Device, size: PhysicalSize<u32>);
+
+    /// Build the imgui interface, drawn over the frame.
+    fn ui(&mut self, _ui: &imgui::Ui) {}
+
+    /// Handle a window event. Resizes and close requests are
+    /// reported through `on_resize` and `should_exit` instead.
+    fn on_event(&mut self, _event: &WindowEvent) {}
+
+    /// Called when the window is resized, after the context has been
+    /// resized.
+    fn on_resize(&mut self, _device: &grr::Device, _size: PhysicalSize<u32>) {}
+
+    /// Checked after every frame; returning `true` ends `run`.
+    fn should_exit(&self) -> bool {
+        false
+    }
+
+    /// Called once when the loop ends, while the device is still
+    /// current, to release GPU resources.
+    fn on_exit(&mut self, _device: &grr::Device) {}
+}
+
+/// Frame pacing options for `run_with_options`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RunOptions {
+    /// Run `App::fixed_update` at this fixed interval. A zero
+    /// interval makes `run_with_options` fail.
+    pub fixed_timestep: Option<std::time::Duration>,
+
+    /// Cap the frame rate, for when vsync is off. Must be positive.
+    pub max_fps: Option<f32>,
+}
+
+/// Run `app` until the window is closed or the app asks to exit.
+///
+/// Takes the parts of a `GrrWindow` separately (see
+/// `GrrWindow::drain`) so that the app may borrow the device.
+pub fn run<A: App>(
+    window: &WindowedContext<PossiblyCurrent>,
+    event_loop: &mut EventLoop<()>,
+    device: &grr::Device,
+    app: &mut A,
+) -> Result<(), Error> {
+    run_with_options(window, event_loop, device, app, RunOptions::default())
+}
+
+/// Run `app` as `run` does, with fixed-timestep updates or a frame
+/// rate cap.
+pub fn run_with_options<A: App>(
+    window: &WindowedContext<PossiblyCurrent>,
+    event_loop: &mut EventLoop<()>,
+    device: &grr::Device,
+    app: &mut A,
+    options: RunOptions,
+) -> Result<(), Error> {
+    let mut imgui = GrrImgui::new(window)?;
+    let mut renderer = imgui.renderer(device)?;
+    let mut timestep = options
+        .fixed_timestep
+        .map(FixedTimestep::new)
+        .transpose()?;
+    let mut limiter = FrameLimiter::new(options.max_fps)?;
+    let mut input = InputState::new();
+
+    app.init(device, window);
+    let size = window.window().inner_size();
+    unsafe { set_full_viewport(device, size.width, size.height) };
+
+    let mut last_update = std::time::Instant::now();
+    let mut result = Ok(());
+
+    event_loop.run_return(|event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+        imgui.on_event(&event, window);
+        input.handle_event(&event);
+
+        match event {
+            Event::NewEvents(_) => {
+                imgui.on_new_events();
+                input.begin_frame();
+            }
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                WindowEvent::Resized(size) => {
+                    window.resize(size);
+                    unsafe { set_full_viewport(device, size.width, size.height) };
+                    app.on_resize(device, size);
+                }
+                event => app.on_event(&event),
+            },
+            Event::MainEventsCleared => {
+                input.update_ui_capture(&imgui);
+                app.input(&input);
+
+                let now = std::time::Instant::now();
+                app.update((now - last_update).as_secs_f32());
+                last_update = now;
+
+                if let Some(timestep) = &mut timestep {
+                    timestep.set_paused(app.paused());
+                    if app.take_single_step() {
+                        timestep.single_step();
+                    }
+                    let frame = timestep.advance();
+                    let dt = timestep.step().as_secs_f32();
+                    for _ in 0..frame.steps {
+                        app.fixed_update(dt);
+                    }
+                    app.interpolate(frame.alpha);
+                }
+
+                imgui.on_events_cleared(window);
+                window.window().request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let size = window.window().inner_size();
+                app.render(device, size);
+
+                let ui = imgui.imgui_context.frame();
+                app.ui(&ui);
+                imgui.imgui_platform.prepare_render(&ui, window.window());
+                let draw_data = ui.render();
+
+                let frame = unsafe { renderer.render(draw_data) }
+                    .map_err(Error::from)
+                    .and_then(|_| window.swap_buffers().map_err(Error::from));
+                if let Err(e) = frame {
+                    result = Err(e);
+                    *control_flow = ControlFlow::Exit;
+                }
+                debug::check_errors();
+
+                if app.should_exit() {
+                    *control_flow = ControlFlow::Exit;
+                }
+                limiter.wait();
+            }
+            _ => {}
+        }
+    });
+
+    app.on_exit(device);
+    result
+}
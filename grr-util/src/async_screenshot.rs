@@ -0,0 +1,280 @@
+//! Screenshots that do not stall the render thread.
+//!
+//! A capture blits the framebuffer into a staging image and starts
+//! an asynchronous read into a pixel pack buffer, guarded by a
+//! fence. Later calls to `AsyncScreenshots::poll` collect finished
+//! reads and hand the pixels to a background thread for encoding.
+use crate::raw_gl::require_gl;
+use crate::screenshot::{flip_rows, Screenshot, ScreenshotError, Staging};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+/// Work run on the encoding thread with the captured pixels.
+type Handler = Box<dyn FnOnce(Screenshot) -> Result<(), ScreenshotError> + Send>;
+
+/// A readback that has been issued but not yet collected.
+struct PendingRead {
+    id: u64,
+    buffer: u32,
+    fence: gl::types::GLsync,
+    width: u32,
+    height: u32,
+    handler: Handler,
+}
+
+/// Outcome of an asynchronous capture, identified by the id returned
+/// when it was started.
+#[derive(Debug)]
+pub struct CaptureResult {
+    pub id: u64,
+    pub result: Result<(), ScreenshotError>,
+}
+
+/// Asynchronous capture of RGBA8 sRGB screenshots.
+///
+/// Captures are completed, and their handlers run, in the order they
+/// were started.
+pub struct AsyncScreenshots<'d> {
+    device: &'d grr::Device,
+    staging: Option<Staging<'d>>,
+    pending: VecDeque<PendingRead>,
+    next_id: u64,
+
+    /// Captures handed to the encoding thread, and results returned
+    /// to the caller.
+    queued: usize,
+    reported: usize,
+
+    jobs: Option<mpsc::Sender<(u64, Screenshot, Handler)>>,
+    results: mpsc::Receiver<CaptureResult>,
+    worker: Option<thread::JoinHandle<()>>,
+}
+
+impl<'d> AsyncScreenshots<'d> {
+    /// Create the capture queue and start its encoding thread.
+    pub fn new(device: &'d grr::Device) -> Self {
+        let (jobs, job_rx) = mpsc::channel::<(u64, Screenshot, Handler)>();
+        let (result_tx, results) = mpsc::channel();
+        let worker = thread::spawn(move || {
+            for (id, shot, handler) in job_rx {
+                let result = handler(shot);
+                if result_tx.send(CaptureResult { id, result }).is_err() {
+                    break;
+                }
+            }
+        });
+
+        AsyncScreenshots {
+            device,
+            staging: None,
+            pending: VecDeque::new(),
+            next_id: 0,
+            queued: 0,
+            reported: 0,
+            jobs: Some(jobs),
+            results,
+            worker: Some(worker),
+        }
+    }
+
+    /// Number of captures whose pixels have not been read back yet.
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Start capturing color attachment 0 of a framebuffer region,
+    /// saving it to `path` once read back.
+    ///
+    /// Returns an id identifying the capture in `poll`.
+    pub fn capture<P: Into<PathBuf>>(
+        &mut self,
+        src_framebuffer: grr::Framebuffer,
+        region: grr::Region,
+        path: P,
+    ) -> Result<u64, ScreenshotError> {
+        let path = path.into();
+        self.capture_with(src_framebuffer, region, 0, move |shot| shot.save(path))
+    }
+
+    /// Start capturing color attachment `index` of a framebuffer
+    /// region, calling `handler` with the pixels on the encoding
+    /// thread once read back.
+    pub fn capture_with<F>(
+        &mut self,
+        src_framebuffer: grr::Framebuffer,
+        region: grr::Region,
+        index: u32,
+        handler: F,
+    ) -> Result<u64, ScreenshotError>
+    where
+        F: FnOnce(Screenshot) -> Result<(), ScreenshotError> + Send + 'static,
+    {
+        if region.w <= 0 || region.h <= 0 {
+            return Err(ScreenshotError::InvalidRegion(region));
+        }
+        let (width, height) = (region.w as u32, region.h as u32);
+        require_gl!(
+            CreateBuffers,
+            NamedBufferData,
+            PixelStorei,
+            BindBuffer,
+            ReadPixels,
+            FenceSync,
+            Flush,
+            ClientWaitSync,
+            GetNamedBufferSubData,
+            DeleteBuffers,
+            DeleteSync,
+        )?;
+
+        // The staging image is reused as long as the size matches;
+        // reads already issued are ordered before later blits.
+        let reuse = matches!(&self.staging, Some(s) if s.width == width && s.height == height);
+        if !reuse {
+            self.staging = None;
+            self.staging = Some(Staging::create(
+                self.device,
+                width,
+                height,
+                grr::Format::R8G8B8A8_SRGB,
+            )?);
+        }
+        let staging = self.staging.as_ref().unwrap();
+        staging.blit_from(src_framebuffer, region, index)?;
+
+        let size = width as usize * height as usize * 4;
+        let (buffer, fence) = unsafe {
+            let mut buffer = 0;
+            gl::CreateBuffers(1, &mut buffer);
+            gl::NamedBufferData(
+                buffer,
+                size as _,
+                std::ptr::null(),
+                gl::STREAM_READ,
+            );
+
+            self.device.bind_read_framebuffer(staging.framebuffer());
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer);
+            gl::ReadPixels(
+                0,
+                0,
+                width as _,
+                height as _,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null_mut(),
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+
+            let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            gl::Flush();
+            (buffer, fence)
+        };
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.push_back(PendingRead {
+            id,
+            buffer,
+            fence,
+            width,
+            height,
+            handler: Box::new(handler),
+        });
+        Ok(id)
+    }
+
+    /// Collect the reads that have completed, without blocking, and
+    /// return the captures whose handlers have finished since the
+    /// last call.
+    pub fn poll(&mut self) -> Vec<CaptureResult> {
+        while let Some(read) = self.pending.front() {
+            if !unsafe { fence_signaled(read.fence, 0) } {
+                break;
+            }
+            let read = self.pending.pop_front().unwrap();
+            self.finish_read(read);
+        }
+        let results: Vec<_> = self.results.try_iter().collect();
+        self.reported += results.len();
+        results
+    }
+
+    /// Block until every capture has been read back and handled, and
+    /// return the results not yet returned by `poll`.
+    pub fn wait_all(&mut self) -> Vec<CaptureResult> {
+        while let Some(read) = self.pending.pop_front() {
+            unsafe {
+                while !fence_signaled(read.fence, 1_000_000) {}
+            }
+            self.finish_read(read);
+        }
+
+        let mut results = Vec::new();
+        while self.reported < self.queued {
+            match self.results.recv() {
+                Ok(r) => {
+                    self.reported += 1;
+                    results.push(r);
+                }
+                Err(_) => break,
+            }
+        }
+        results
+    }
+
+    /// Copy a completed read out of its buffer and queue it for
+    /// encoding.
+    fn finish_read(&mut self, read: PendingRead) {
+        let size = read.width as usize * read.height as usize * 4;
+        let mut pixels = vec![0u8; size];
+        unsafe {
+            gl::GetNamedBufferSubData(read.buffer, 0, size as _, pixels.as_mut_ptr() as *mut _);
+            gl::DeleteBuffers(1, &read.buffer);
+            gl::DeleteSync(read.fence);
+        }
+
+        flip_rows(&mut pixels, read.width as usize * 4, read.height as usize);
+        let shot = Screenshot {
+            width: read.width,
+            height: read.height,
+            pixels,
+        };
+        if let Some(jobs) = &self.jobs {
+            if jobs.send((read.id, shot, read.handler)).is_ok() {
+                self.queued += 1;
+            }
+        }
+    }
+}
+
+impl<'d> Drop for AsyncScreenshots<'d> {
+    /// Finish outstanding captures, then stop the encoding thread.
+    fn drop(&mut self) {
+        while let Some(read) = self.pending.pop_front() {
+            unsafe {
+                while !fence_signaled(read.fence, 1_000_000) {}
+            }
+            self.finish_read(read);
+        }
+        self.jobs = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Check whether a fence has been signaled, waiting up to `timeout`
+/// nanoseconds.
+unsafe fn fence_signaled(fence: gl::types::GLsync, timeout: u64) -> bool {
+    match gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) {
+        gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED => true,
+        // Treat a lost context as complete, rather than spinning
+        // forever.
+        gl::WAIT_FAILED => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,328 @@
+//! Packing of many small images into texture atlas pages.
+use crate::image_manager::{self, ImageId, ImageManager, ImageRegion};
+use nalgebra::Vector4;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum AtlasError {
+    #[error("atlas entry {0} does not fit in a page")]
+    TooLarge(usize),
+
+    #[error("atlas entries do not fit in {0} pages")]
+    TooManyPages(u32),
+
+    #[error("atlas page of {0}x{1} texels is too large")]
+    PageTooLarge(u32, u32),
+
+    #[error("failed to decode atlas entry")]
+    Decode(#[from] image::ImageError),
+
+    #[error("image manager error")]
+    Image(#[from] image_manager::Error),
+}
+
+/// Location of a rectangle placed by `RectPacker`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedRect {
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A horizontal strip of a page, filled from left to right.
+struct Shelf {
+    y: u32,
+    height: u32,
+    next_x: u32,
+}
+
+struct Page {
+    shelves: Vec<Shelf>,
+    next_y: u32,
+}
+
+/// Shelf-based rectangle packer.
+///
+/// Rectangles are sorted by height and placed on the first shelf
+/// that fits them, opening new shelves and pages as needed.
+#[derive(Debug, Clone, Copy)]
+pub struct RectPacker {
+    pub page_width: u32,
+    pub page_height: u32,
+
+    /// Empty texels kept around each rectangle, to avoid bleeding
+    /// when filtering.
+    pub padding: u32,
+
+    /// Maximum number of pages, or `None` for no limit.
+    pub max_pages: Option<u32>,
+}
+
+impl RectPacker {
+    pub fn new(page_width: u32, page_height: u32) -> RectPacker {
+        RectPacker {
+            page_width,
+            page_height,
+            padding: 0,
+            max_pages: None,
+        }
+    }
+
+    /// Place each `(width, height)` rectangle, returning the
+    /// placements in the same order as `sizes`.
+    pub fn pack(&self, sizes: &[(u32, u32)]) -> Result<Vec<PackedRect>, AtlasError> {
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse((sizes[i].1, sizes[i].0)));
+
+        let mut pages: Vec<Page> = vec![];
+        let mut placed = vec![None; sizes.len()];
+
+        for i in order {
+            let (width, height) = sizes[i];
+            let padded = |x: u32| x.checked_add(self.padding.checked_mul(2)?);
+            let (w, h) = match (padded(width), padded(height)) {
+                (Some(w), Some(h)) if w <= self.page_width && h <= self.page_height => (w, h),
+                _ => return Err(AtlasError::TooLarge(i)),
+            };
+
+            let (page, x, y) = match self.place(&mut pages, w, h) {
+                Some(p) => p,
+                None => {
+                    let num_pages = pages.len() as u32;
+                    if self.max_pages.map_or(false, |m| num_pages >= m) {
+                        return Err(AtlasError::TooManyPages(num_pages));
+                    }
+                    pages.push(Page {
+                        shelves: vec![],
+                        next_y: 0,
+                    });
+                    self.place(&mut pages, w, h)
+                        .expect("rectangle fits in an empty page")
+                }
+            };
+
+            placed[i] = Some(PackedRect {
+                page,
+                x: x + self.padding,
+                y: y + self.padding,
+                width,
+                height,
+            });
+        }
+
+        Ok(placed.into_iter().map(Option::unwrap).collect())
+    }
+
+    /// Find room for a `w` by `h` rectangle in the existing pages.
+    fn place(&self, pages: &mut [Page], w: u32, h: u32) -> Option<(u32, u32, u32)> {
+        for (pi, page) in pages.iter_mut().enumerate() {
+            for shelf in page.shelves.iter_mut() {
+                if shelf.height >= h && w <= self.page_width - shelf.next_x {
+                    let x = shelf.next_x;
+                    shelf.next_x += w;
+                    return Some((pi as u32, x, shelf.y));
+                }
+            }
+
+            if h <= self.page_height - page.next_y {
+                let y = page.next_y;
+                page.shelves.push(Shelf {
+                    y,
+                    height: h,
+                    next_x: w,
+                });
+                page.next_y += h;
+                return Some((pi as u32, 0, y));
+            }
+        }
+        None
+    }
+}
+
+/// Texture coordinates of an atlas entry.
+///
+/// `v` increases with the row index of the source data, matching the
+/// layout used by `ImageManager::create_image_from_ndarray`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasEntry {
+    pub u0: f32,
+    pub v0: f32,
+    pub u1: f32,
+    pub v1: f32,
+
+    /// Array layer of the atlas image holding the entry.
+    pub layer: u32,
+}
+
+/// Atlas pages uploaded to a managed image.
+pub struct Atlas {
+    /// 2D image for a single page, or 2D array image with one layer
+    /// per page.
+    pub image: ImageId,
+    pub num_pages: u32,
+
+    /// Entries, in the order they were added to the builder.
+    pub entries: Vec<AtlasEntry>,
+}
+
+/// Collects images to be packed into an `Atlas`.
+pub struct AtlasBuilder {
+    packer: RectPacker,
+    srgb: bool,
+    flip_vertically: bool,
+    images: Vec<ndarray::Array2<Vector4<u8>>>,
+}
+
+impl AtlasBuilder {
+    pub fn new(page_width: u32, page_height: u32) -> AtlasBuilder {
+        AtlasBuilder {
+            packer: RectPacker::new(page_width, page_height),
+            srgb: true,
+            flip_vertically: true,
+            images: vec![],
+        }
+    }
+
+    pub fn padding(mut self, padding: u32) -> AtlasBuilder {
+        self.packer.padding = padding;
+        self
+    }
+
+    pub fn max_pages(mut self, max_pages: impl Into<Option<u32>>) -> AtlasBuilder {
+        self.packer.max_pages = max_pages.into();
+        self
+    }
+
+    /// Store the atlas in an sRGB format.
+    pub fn srgb(self, srgb: bool) -> AtlasBuilder {
+        AtlasBuilder { srgb, ..self }
+    }
+
+    /// Flip files added with `add_file` vertically, as
+    /// `ImageLoadOptions::flip_vertically` does for
+    /// `ImageManager::load_image`. Defaults to true, so that atlas
+    /// entries are oriented like images loaded with default options.
+    pub fn flip_vertically(self, flip_vertically: bool) -> AtlasBuilder {
+        AtlasBuilder {
+            flip_vertically,
+            ..self
+        }
+    }
+
+    /// Add an RGBA image, returning the index of its entry.
+    pub fn add_ndarray(&mut self, data: ndarray::Array2<Vector4<u8>>) -> usize {
+        self.images.push(data);
+        self.images.len() - 1
+    }
+
+    /// Decode and add an image file, returning the index of its entry.
+    ///
+    /// Rows are stored bottom row first, unless disabled with
+    /// `flip_vertically`.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, AtlasError> {
+        let mut img = image::open(path)?.to_rgba8();
+        if self.flip_vertically {
+            image::imageops::flip_vertical_in_place(&mut img);
+        }
+        let (w, h) = img.dimensions();
+        let texels: Vec<_> = img
+            .pixels()
+            .map(|p| Vector4::new(p[0], p[1], p[2], p[3]))
+            .collect();
+        let data = ndarray::Array2::from_shape_vec((h as usize, w as usize), texels)
+            .expect("pixel count matches image dimensions");
+        Ok(self.add_ndarray(data))
+    }
+
+    /// Pack the entries and upload the pages.
+    pub fn build(self, images: &mut ImageManager) -> Result<Atlas, AtlasError> {
+        let sizes: Vec<_> = self
+            .images
+            .iter()
+            .map(|a| (a.ncols() as u32, a.nrows() as u32))
+            .collect();
+        let placements = self.packer.pack(&sizes)?;
+        let num_pages = placements.iter().map(|p| p.page + 1).max().unwrap_or(1);
+
+        let (pw, ph) = (self.packer.page_width, self.packer.page_height);
+        let page_bytes = (pw as usize)
+            .checked_mul(ph as usize)
+            .and_then(|n| n.checked_mul(4))
+            .ok_or(AtlasError::PageTooLarge(pw, ph))?;
+        let format = if self.srgb {
+            grr::Format::R8G8B8A8_SRGB
+        } else {
+            grr::Format::R8G8B8A8_UNORM
+        };
+        let image = images.create_image(
+            grr::ImageType::D2 {
+                width: pw,
+                height: ph,
+                layers: num_pages,
+                samples: 1,
+            },
+            format,
+            1,
+        )?;
+
+        if let Err(e) = self.upload(images, image, &placements, num_pages, page_bytes) {
+            images.delete_image(image);
+            return Err(e);
+        }
+
+        let entries = placements
+            .iter()
+            .map(|p| AtlasEntry {
+                u0: p.x as f32 / pw as f32,
+                v0: p.y as f32 / ph as f32,
+                u1: (p.x + p.width) as f32 / pw as f32,
+                v1: (p.y + p.height) as f32 / ph as f32,
+                layer: p.page,
+            })
+            .collect();
+
+        Ok(Atlas {
+            image,
+            num_pages,
+            entries,
+        })
+    }
+
+    /// Clear the pages of `image` and upload every entry.
+    fn upload(
+        &self,
+        images: &mut ImageManager,
+        image: ImageId,
+        placements: &[PackedRect],
+        num_pages: u32,
+        page_bytes: usize,
+    ) -> Result<(), AtlasError> {
+        // Clear the pages so that padding is transparent.
+        let blank = vec![0u8; page_bytes];
+        for page in 0..num_pages {
+            images.update_region(
+                image,
+                &ImageRegion::whole(0, page),
+                grr::BaseFormat::RGBA,
+                &blank,
+                false,
+            )?;
+        }
+
+        for (data, p) in self.images.iter().zip(placements.iter()) {
+            let region = ImageRegion {
+                offset: grr::Offset {
+                    x: p.x as i32,
+                    y: p.y as i32,
+                    z: 0,
+                },
+                ..ImageRegion::whole(0, p.page)
+            };
+            images.update_region_from_ndarray(image, &region, data, false)?;
+        }
+        Ok(())
+    }
+}
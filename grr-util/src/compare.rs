@@ -0,0 +1,226 @@
+//! Comparison of images against reference images, for golden-image
+//! tests.
+//!
+//! Images are compared as RGBA float values, which are normalized to
+//! [0, 1] for fixed-point formats.
+use crate::image_manager::{self, ImageId, ImageManager, ImageRegion};
+use nalgebra::Vector4;
+use ndarray::Array2;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CompareError {
+    #[error("image sizes differ: {0:?} and {1:?}")]
+    SizeMismatch((usize, usize), (usize, usize)),
+
+    #[error("failed to load or save image")]
+    Io(#[from] image::ImageError),
+
+    #[error("image manager error")]
+    Image(#[from] image_manager::Error),
+}
+
+/// Largest absolute difference allowed in each channel before a
+/// pixel counts as differing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance(pub Vector4<f32>);
+
+impl Tolerance {
+    /// The same tolerance in every channel.
+    pub fn uniform(t: f32) -> Tolerance {
+        Tolerance(Vector4::repeat(t))
+    }
+
+    /// Tolerance on the color channels, ignoring alpha.
+    pub fn rgb(t: f32) -> Tolerance {
+        Tolerance(Vector4::new(t, t, t, std::f32::INFINITY))
+    }
+}
+
+impl Default for Tolerance {
+    /// Allows one step of 8-bit quantization.
+    fn default() -> Tolerance {
+        Tolerance::uniform(1.0 / 255.0)
+    }
+}
+
+/// Result of comparing two images.
+#[derive(Debug, Clone)]
+pub struct ImageDiff {
+    /// Largest absolute error in each channel. Infinite if the
+    /// channel differs by a NaN or infinity anywhere.
+    pub max_abs_error: Vector4<f32>,
+
+    /// Root-mean-square error over all channels.
+    pub rmse: f32,
+
+    /// Peak signal-to-noise ratio in dB, with a peak of 1. Infinite
+    /// for identical images.
+    pub psnr: f32,
+
+    /// Number of pixels with an error above the tolerance in any
+    /// channel. Pixels where either image is NaN or infinite always
+    /// count as differing.
+    pub num_differing: usize,
+
+    /// Largest absolute error over the channels of each pixel.
+    pub error_map: Array2<f32>,
+}
+
+impl ImageDiff {
+    /// No pixel differs by more than the tolerance.
+    pub fn passed(&self) -> bool {
+        self.num_differing == 0
+    }
+
+    /// Render the per-pixel error as a black-red-yellow-white heat
+    /// map, reaching white at an error of `scale`.
+    ///
+    /// If `scale` is `None`, the largest finite error in the image is
+    /// used. Non-finite errors are drawn white.
+    pub fn heat_map(&self, scale: Option<f32>) -> Array2<Vector4<u8>> {
+        let scale = scale
+            .unwrap_or_else(|| {
+                self.error_map
+                    .iter()
+                    .cloned()
+                    .filter(|e| e.is_finite())
+                    .fold(0.0, f32::max)
+            })
+            .max(std::f32::MIN_POSITIVE);
+
+        let ramp = |t: f32| ((t.max(0.0).min(1.0)) * 255.0).round() as u8;
+        self.error_map.mapv(|e| {
+            let t = 3.0 * e / scale;
+            Vector4::new(ramp(t), ramp(t - 1.0), ramp(t - 2.0), 255)
+        })
+    }
+
+    /// Write the heat map from `heat_map` to a PNG file.
+    pub fn save_heat_map<P: AsRef<Path>>(
+        &self,
+        path: P,
+        scale: Option<f32>,
+    ) -> Result<(), CompareError> {
+        let map = self.heat_map(scale);
+        let (h, w) = map.dim();
+        let buf: Vec<u8> = map.iter().flat_map(|v| v.iter().cloned()).collect();
+        image::save_buffer(path, &buf, w as u32, h as u32, image::ColorType::Rgba8)?;
+        Ok(())
+    }
+}
+
+/// Compare two RGBA images, given as arrays indexed by `(row, column)`.
+pub fn compare_arrays(
+    a: &Array2<Vector4<f32>>,
+    b: &Array2<Vector4<f32>>,
+    tolerance: &Tolerance,
+) -> Result<ImageDiff, CompareError> {
+    if a.dim() != b.dim() {
+        return Err(CompareError::SizeMismatch(a.dim(), b.dim()));
+    }
+
+    let mut max_abs_error = Vector4::zeros();
+    let mut sum_sq = 0.0f64;
+    let mut num_differing = 0;
+    let error_map = ndarray::Zip::from(a).and(b).map_collect(|x, y| {
+        // NaN compares false against any tolerance, so non-finite
+        // errors are made infinite and always count.
+        let err = (x - y)
+            .abs()
+            .map(|e| if e.is_finite() { e } else { std::f32::INFINITY });
+        max_abs_error = max_abs_error.sup(&err);
+        sum_sq += err.iter().map(|&e| (e as f64) * (e as f64)).sum::<f64>();
+        if err
+            .iter()
+            .zip(tolerance.0.iter())
+            .any(|(e, t)| e.is_infinite() || e > t)
+        {
+            num_differing += 1;
+        }
+        err.max()
+    });
+
+    let mse = sum_sq / (4 * a.len()).max(1) as f64;
+    let psnr = if mse == 0.0 {
+        std::f32::INFINITY
+    } else {
+        (-10.0 * mse.log10()) as f32
+    };
+
+    Ok(ImageDiff {
+        max_abs_error,
+        rmse: mse.sqrt() as f32,
+        psnr,
+        num_differing,
+        error_map,
+    })
+}
+
+/// Load an image file as normalized RGBA floats.
+pub fn load_rgba_f32<P: AsRef<Path>>(path: P) -> Result<Array2<Vector4<f32>>, CompareError> {
+    let img = image::open(path)?.to_rgba32f();
+    let (w, h) = img.dimensions();
+    let texels: Vec<_> = img
+        .pixels()
+        .map(|p| Vector4::new(p[0], p[1], p[2], p[3]))
+        .collect();
+    Ok(Array2::from_shape_vec((h as usize, w as usize), texels)
+        .expect("pixel count matches image dimensions"))
+}
+
+/// Read a level and layer of a managed image as RGBA floats, in the
+/// same row order as `load_rgba_f32`.
+///
+/// Images are stored bottom-up, so the rows are flipped.
+pub fn read_rgba_f32(
+    images: &ImageManager,
+    image_id: ImageId,
+    level: u32,
+    layer: u32,
+) -> Result<Array2<Vector4<f32>>, CompareError> {
+    let data = images.read_image_region::<f32, ndarray::Ix2, nalgebra::U4>(
+        image_id,
+        &ImageRegion::whole(level, layer),
+    )?;
+    Ok(data.slice(ndarray::s![..;-1, ..]).to_owned())
+}
+
+/// Compare two image files.
+pub fn compare_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    a: P,
+    b: Q,
+    tolerance: &Tolerance,
+) -> Result<ImageDiff, CompareError> {
+    compare_arrays(&load_rgba_f32(a)?, &load_rgba_f32(b)?, tolerance)
+}
+
+/// Compare level 0, layer 0 of two managed images.
+pub fn compare_images(
+    images: &ImageManager,
+    a: ImageId,
+    b: ImageId,
+    tolerance: &Tolerance,
+) -> Result<ImageDiff, CompareError> {
+    compare_arrays(
+        &read_rgba_f32(images, a, 0, 0)?,
+        &read_rgba_f32(images, b, 0, 0)?,
+        tolerance,
+    )
+}
+
+/// Compare level 0, layer 0 of a managed image against a reference
+/// image file.
+pub fn compare_image_to_file<P: AsRef<Path>>(
+    images: &ImageManager,
+    image_id: ImageId,
+    reference: P,
+    tolerance: &Tolerance,
+) -> Result<ImageDiff, CompareError> {
+    compare_arrays(
+        &read_rgba_f32(images, image_id, 0, 0)?,
+        &load_rgba_f32(reference)?,
+        tolerance,
+    )
+}
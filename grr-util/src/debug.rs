@@ -0,0 +1,309 @@
+//! Routing of OpenGL debug messages.
+//!
+//! `grr` takes a bare function as its debug callback, so the routing
+//! configuration lives in per-thread `DebugOutput`s, installed by
+//! `GrrBuilder` (or `install`) and read by `route_message`. `grr`
+//! enables synchronous debug output, so messages arrive on the thread
+//! whose context caused them.
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A debug message reported by the driver.
+#[derive(Debug, Clone)]
+pub struct DebugMessage<'a> {
+    pub report: grr::DebugReport,
+    pub source: grr::DebugSource,
+    pub ty: grr::DebugType,
+    pub id: u32,
+    pub message: &'a str,
+}
+
+impl<'a> DebugMessage<'a> {
+    /// Log level matching the severity of the message.
+    pub fn level(&self) -> log::Level {
+        if self.report.contains(grr::DebugReport::ERROR) {
+            log::Level::Error
+        } else if self
+            .report
+            .intersects(grr::DebugReport::WARNING | grr::DebugReport::PERFORMANCE_WARNING)
+        {
+            log::Level::Warn
+        } else {
+            log::Level::Debug
+        }
+    }
+}
+
+/// First message of error severity handled by a `DebugOutput`.
+#[derive(Debug, Clone)]
+pub struct DebugError {
+    pub source: grr::DebugSource,
+    pub ty: grr::DebugType,
+    pub id: u32,
+    pub message: String,
+}
+
+impl fmt::Display for DebugError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OpenGL error {}: {}", self.id, self.message)
+    }
+}
+
+impl std::error::Error for DebugError {}
+
+/// Number of distinct messages remembered for deduplication. The
+/// record is cleared once full, so a message may then repeat.
+pub const DEDUP_CAPACITY: usize = 1024;
+
+type Sink = Box<dyn FnMut(&DebugMessage) + Send>;
+
+/// Where debug messages go, and which are dropped.
+pub struct DebugOutput {
+    sink: Option<Sink>,
+    ignored_ids: HashSet<u32>,
+    ignored_sources: Vec<grr::DebugSource>,
+    ignored_types: Vec<grr::DebugType>,
+    dedup: bool,
+    seen: HashSet<(u32, String)>,
+    first_error: Option<DebugError>,
+    panic_on_error: bool,
+}
+
+impl DebugOutput {
+    /// Send messages to the `log` crate, under the `gl` target.
+    pub fn log() -> DebugOutput {
+        DebugOutput {
+            sink: None,
+            ignored_ids: HashSet::new(),
+            ignored_sources: Vec::new(),
+            ignored_types: Vec::new(),
+            dedup: false,
+            seen: HashSet::new(),
+            first_error: None,
+            panic_on_error: false,
+        }
+    }
+
+    /// Send messages to `f`.
+    ///
+    /// `f` runs inside the driver's callback, and must not make GL
+    /// calls.
+    pub fn sink<F: FnMut(&DebugMessage) + Send + 'static>(f: F) -> DebugOutput {
+        DebugOutput {
+            sink: Some(Box::new(f)),
+            ..DebugOutput::log()
+        }
+    }
+
+    /// Drop messages with this id.
+    pub fn ignore_id(mut self, id: u32) -> DebugOutput {
+        self.ignored_ids.insert(id);
+        self
+    }
+
+    /// Drop messages from this source.
+    pub fn ignore_source(mut self, source: grr::DebugSource) -> DebugOutput {
+        self.ignored_sources.push(source);
+        self
+    }
+
+    /// Drop messages of this type.
+    pub fn ignore_type(mut self, ty: grr::DebugType) -> DebugOutput {
+        self.ignored_types.push(ty);
+        self
+    }
+
+    /// Only pass on the first occurrence of each message, among the
+    /// last `DEDUP_CAPACITY` distinct ones.
+    pub fn dedup(self, dedup: bool) -> DebugOutput {
+        DebugOutput { dedup, ..self }
+    }
+
+    /// Panic on the first error-severity message, for tests.
+    ///
+    /// Unwinding out of the driver's callback would abort the
+    /// process, so the callback only records the error, and the panic
+    /// happens at the next `check_errors` on this thread. `app::run`
+    /// checks after every frame.
+    pub fn panic_on_error(self, panic_on_error: bool) -> DebugOutput {
+        DebugOutput {
+            panic_on_error,
+            ..self
+        }
+    }
+
+    /// Take the first error-severity message handled since the last
+    /// call, e.g. to fail a test after rendering.
+    pub fn take_error(&mut self) -> Option<DebugError> {
+        self.first_error.take()
+    }
+
+    /// Handle a message.
+    pub fn handle(&mut self, msg: &DebugMessage) {
+        if self.ignored_ids.contains(&msg.id)
+            || self.ignored_sources.contains(&msg.source)
+            || self.ignored_types.contains(&msg.ty)
+        {
+            return;
+        }
+        if self.dedup {
+            let key = (msg.id, msg.message.to_owned());
+            if self.seen.contains(&key) {
+                return;
+            }
+            if self.seen.len() >= DEDUP_CAPACITY {
+                self.seen.clear();
+            }
+            self.seen.insert(key);
+        }
+
+        if self.first_error.is_none() && msg.level() == log::Level::Error {
+            self.first_error = Some(DebugError {
+                source: msg.source,
+                ty: msg.ty,
+                id: msg.id,
+                message: msg.message.to_owned(),
+            });
+        }
+
+        match &mut self.sink {
+            Some(sink) => sink(msg),
+            None => log::log!(
+                target: "gl",
+                msg.level(),
+                "{} {:?} ({:?}/{:?}): {}",
+                msg.id,
+                msg.report,
+                msg.source,
+                msg.ty,
+                msg.message
+            ),
+        }
+    }
+}
+
+impl Default for DebugOutput {
+    fn default() -> DebugOutput {
+        DebugOutput::log()
+    }
+}
+
+thread_local! {
+    /// Outputs installed on this thread, newest last.
+    static OUTPUTS: RefCell<Vec<(u64, DebugOutput)>> = RefCell::new(Vec::new());
+    static NEXT_ID: Cell<u64> = Cell::new(0);
+}
+
+/// Keeps a `DebugOutput` installed on the current thread; dropping it
+/// uninstalls the output.
+pub struct OutputGuard {
+    id: u64,
+    // Outputs are per-thread, so the guard must stay on its thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl OutputGuard {
+    /// Take the first error recorded by this output, see
+    /// `DebugOutput::take_error`.
+    pub fn take_error(&self) -> Option<DebugError> {
+        OUTPUTS.with(|outputs| {
+            outputs
+                .borrow_mut()
+                .iter_mut()
+                .find(|(id, _)| *id == self.id)
+                .and_then(|(_, output)| output.take_error())
+        })
+    }
+}
+
+impl Drop for OutputGuard {
+    fn drop(&mut self) {
+        // The thread-local may already be gone during thread exit.
+        let _ = OUTPUTS.try_with(|outputs| {
+            outputs.borrow_mut().retain(|(id, _)| *id != self.id);
+        });
+    }
+}
+
+/// Install `output` on the current thread until the guard is dropped.
+///
+/// Messages go to the most recently installed output that is still
+/// alive, so building a second context on the same thread takes over
+/// the messages until it is dropped, and then hands them back.
+pub fn install(output: DebugOutput) -> OutputGuard {
+    let id = NEXT_ID.with(|next| {
+        next.set(next.get() + 1);
+        next.get()
+    });
+    OUTPUTS.with(|outputs| outputs.borrow_mut().push((id, output)));
+    OutputGuard {
+        id,
+        _not_send: PhantomData,
+    }
+}
+
+/// Take the first error recorded by the current output of this thread.
+pub fn take_error() -> Option<DebugError> {
+    OUTPUTS.with(|outputs| {
+        outputs
+            .borrow_mut()
+            .last_mut()
+            .and_then(|(_, output)| output.take_error())
+    })
+}
+
+/// Panic with the recorded error if the current output of this
+/// thread was built with `panic_on_error` and has seen an error.
+/// Does nothing for other outputs, leaving their errors to
+/// `take_error`.
+pub fn check_errors() {
+    let error = OUTPUTS.with(|outputs| {
+        outputs
+            .borrow_mut()
+            .last_mut()
+            .filter(|(_, output)| output.panic_on_error)
+            .and_then(|(_, output)| output.take_error())
+    });
+    if let Some(error) = error {
+        panic!("{}", error);
+    }
+}
+
+/// Debug callback for `grr::Debug::Enable`, passing messages to the
+/// current output of this thread, or to the `log` crate if there is
+/// none.
+pub fn route_message(
+    report: grr::DebugReport,
+    source: grr::DebugSource,
+    ty: grr::DebugType,
+    id: u32,
+    message: &str,
+) {
+    let msg = DebugMessage {
+        report,
+        source,
+        ty,
+        id,
+        message,
+    };
+
+    // This runs inside the driver's callback, where a panic would
+    // abort, so fall back to logging instead of failing.
+    let routed = OUTPUTS
+        .try_with(|outputs| match outputs.try_borrow_mut() {
+            Ok(mut outputs) => match outputs.last_mut() {
+                Some((_, output)) => {
+                    output.handle(&msg);
+                    true
+                }
+                None => false,
+            },
+            Err(_) => false,
+        })
+        .unwrap_or(false);
+    if !routed {
+        DebugOutput::log().handle(&msg);
+    }
+}
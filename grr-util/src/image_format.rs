@@ -10,6 +10,9 @@ pub trait TexelBaseType: Zero + PartialEq + Clone + Copy + std::fmt::Debug + 'st
 // ndarray dimensions that can be interpreted as a texture dimension.
 pub trait TextureDim: ndarray::Dimension {
     fn image_type(&self) -> grr::ImageType;
+
+    /// Return the array shape that holds a region of size `extent`.
+    fn from_extent(extent: grr::Extent) -> Self;
 }
 
 impl TextureDim for ndarray::Ix1 {
@@ -19,6 +22,10 @@ impl TextureDim for ndarray::Ix1 {
             layers: 1,
         }
     }
+
+    fn from_extent(extent: grr::Extent) -> Self {
+        ndarray::Dim([extent.width as usize])
+    }
 }
 
 impl TextureDim for ndarray::Ix2 {
@@ -30,6 +37,10 @@ impl TextureDim for ndarray::Ix2 {
             samples: 1,
         }
     }
+
+    fn from_extent(extent: grr::Extent) -> Self {
+        ndarray::Dim([extent.height as usize, extent.width as usize])
+    }
 }
 
 impl TextureDim for ndarray::Ix3 {
@@ -37,9 +48,17 @@ impl TextureDim for ndarray::Ix3 {
         grr::ImageType::D3 {
             width: self[2] as u32,
             height: self[1] as u32,
-            depth: self[2] as u32,
+            depth: self[0] as u32,
         }
     }
+
+    fn from_extent(extent: grr::Extent) -> Self {
+        ndarray::Dim([
+            extent.depth as usize,
+            extent.height as usize,
+            extent.width as usize,
+        ])
+    }
 }
 
 pub trait TextureComponentDim: nalgebra::Dim + nalgebra::DimName {
@@ -80,6 +99,101 @@ impl TexelBaseType for i16 {
 impl TexelBaseType for i32 {
     const layout: grr::FormatLayout = grr::FormatLayout::I32;
 }
+impl TexelBaseType for half::f16 {
+    const layout: grr::FormatLayout = grr::FormatLayout::F16;
+}
+
+/// A single host texel, made of one to four components of the same
+/// `TexelBaseType`.
+///
+/// The base format and layout of the texel are known at compile
+/// time, so arrays of texels can be uploaded to and read back from
+/// images without specifying a format. Can be derived for
+/// `#[repr(C)]` structs with `#[derive(TexelType)]`.
+///
+/// # Safety
+///
+/// Implementors must consist of exactly
+/// `base_format_num_components(Self::base_format)` values of
+/// `Self::Component`, with no padding, and any bit pattern must be a
+/// valid value.
+pub unsafe trait TexelType: Copy + 'static {
+    type Component: TexelBaseType;
+    const base_format: grr::BaseFormat;
+
+    /// Whether the color components are sRGB-encoded.
+    const srgb: bool = false;
+
+    /// The image format matching this texel, if one exists.
+    fn format() -> Option<grr::Format> {
+        let format = format_from_base_and_layout(
+            Self::base_format,
+            <Self::Component as TexelBaseType>::layout,
+        )?;
+        if Self::srgb {
+            srgb_pair(format)
+        } else {
+            Some(format)
+        }
+    }
+}
+
+macro_rules! impl_texel_scalar {
+    ($($ty:ty),*) => {
+        $(
+            unsafe impl TexelType for $ty {
+                type Component = $ty;
+                const base_format: grr::BaseFormat = grr::BaseFormat::R;
+            }
+        )*
+    };
+}
+
+macro_rules! impl_texel_array {
+    ($($n:expr => $bf:ident),*) => {
+        $(
+            unsafe impl<T: TexelBaseType> TexelType for [T; $n] {
+                type Component = T;
+                const base_format: grr::BaseFormat = grr::BaseFormat::$bf;
+            }
+        )*
+    };
+}
+
+impl_texel_scalar!(f32, u8, u16, u32, i8, i16, i32, half::f16);
+impl_texel_array!(1 => R, 2 => RG, 3 => RGB, 4 => RGBA);
+
+unsafe impl<PC: TexelBaseType, CD: TextureComponentDim> TexelType for nalgebra::VectorN<PC, CD>
+where
+    nalgebra::DefaultAllocator: nalgebra::base::allocator::Allocator<PC, CD>,
+    nalgebra::VectorN<PC, CD>: Copy,
+{
+    type Component = PC;
+    const base_format: grr::BaseFormat = CD::base_format;
+}
+
+unsafe impl<T: TexelBaseType> TexelType for palette::rgb::Rgb<palette::encoding::Srgb, T> {
+    type Component = T;
+    const base_format: grr::BaseFormat = grr::BaseFormat::RGB;
+    const srgb: bool = true;
+}
+unsafe impl<T: TexelBaseType> TexelType for palette::rgb::Rgba<palette::encoding::Srgb, T> {
+    type Component = T;
+    const base_format: grr::BaseFormat = grr::BaseFormat::RGBA;
+    const srgb: bool = true;
+}
+unsafe impl<T: TexelBaseType> TexelType
+    for palette::rgb::Rgb<palette::encoding::Linear<palette::encoding::Srgb>, T>
+{
+    type Component = T;
+    const base_format: grr::BaseFormat = grr::BaseFormat::RGB;
+}
+unsafe impl<T: TexelBaseType> TexelType
+    for palette::rgb::Rgba<palette::encoding::Linear<palette::encoding::Srgb>, T>
+{
+    type Component = T;
+    const base_format: grr::BaseFormat = grr::BaseFormat::RGBA;
+}
 
 /// Return a full format from a base format and a format layout.
 pub fn format_from_base_and_layout(
@@ -95,17 +209,84 @@ pub fn format_from_base_and_layout(
         (grr::BaseFormat::RG, grr::FormatLayout::U8) => Some(grr::Format::R8G8_UNORM),
         (grr::BaseFormat::RGB, grr::FormatLayout::U8) => Some(grr::Format::R8G8B8_UNORM),
         (grr::BaseFormat::RGBA, grr::FormatLayout::U8) => Some(grr::Format::R8G8B8A8_UNORM),
+        (grr::BaseFormat::R, grr::FormatLayout::U16) => Some(grr::Format::R16_UNORM),
+        (grr::BaseFormat::RG, grr::FormatLayout::U16) => Some(grr::Format::R16G16_UNORM),
+        (grr::BaseFormat::RGB, grr::FormatLayout::U16) => Some(grr::Format::R16G16B16_UNORM),
+        (grr::BaseFormat::RGBA, grr::FormatLayout::U16) => Some(grr::Format::R16G16B16A16_UNORM),
+        (grr::BaseFormat::R, grr::FormatLayout::F16) => Some(grr::Format::R16_SFLOAT),
+        (grr::BaseFormat::RG, grr::FormatLayout::F16) => Some(grr::Format::R16G16_SFLOAT),
+        (grr::BaseFormat::RGB, grr::FormatLayout::F16) => Some(grr::Format::R16G16B16_SFLOAT),
+        (grr::BaseFormat::RGBA, grr::FormatLayout::F16) => Some(grr::Format::R16G16B16A16_SFLOAT),
         _ => None,
     }
 }
 
+/// Return the number of components in a base format.
+pub fn base_format_num_components(bf: grr::BaseFormat) -> usize {
+    match bf {
+        grr::BaseFormat::R => 1,
+        grr::BaseFormat::RG => 2,
+        grr::BaseFormat::RGB => 3,
+        grr::BaseFormat::RGBA => 4,
+        grr::BaseFormat::Depth => 1,
+        grr::BaseFormat::DepthStencil => 2,
+        grr::BaseFormat::Stencil => 1,
+    }
+}
+
+/// Return the base format and layout used to upload host data of an
+/// uncompressed format without conversion.
+pub fn format_host_layout(format: grr::Format) -> Option<(grr::BaseFormat, grr::FormatLayout)> {
+    use grr::{BaseFormat as B, Format::*, FormatLayout as L};
+    let layout = match format {
+        R8_UNORM => (B::R, L::U8),
+        R8G8_UNORM => (B::RG, L::U8),
+        R8G8B8_UNORM | R8G8B8_SRGB => (B::RGB, L::U8),
+        R8G8B8A8_UNORM | R8G8B8A8_SRGB => (B::RGBA, L::U8),
+        R16_UNORM => (B::R, L::U16),
+        R16G16_UNORM => (B::RG, L::U16),
+        R16G16B16_UNORM => (B::RGB, L::U16),
+        R16G16B16A16_UNORM => (B::RGBA, L::U16),
+        R16_SFLOAT => (B::R, L::F16),
+        R16G16_SFLOAT => (B::RG, L::F16),
+        R16G16B16_SFLOAT => (B::RGB, L::F16),
+        R16G16B16A16_SFLOAT => (B::RGBA, L::F16),
+        R32_SFLOAT => (B::R, L::F32),
+        R32G32_SFLOAT => (B::RG, L::F32),
+        R32G32B32_SFLOAT => (B::RGB, L::F32),
+        R32G32B32A32_SFLOAT => (B::RGBA, L::F32),
+        _ => return None,
+    };
+    Some(layout)
+}
+
+/// Return true if host data of `base_format` and `layout` can be
+/// uploaded to an image of `format`.
+///
+/// Formats with a known host layout require an exact match; others
+/// only require matching base formats.
+pub fn host_layout_compatible(
+    format: grr::Format,
+    base_format: grr::BaseFormat,
+    layout: grr::FormatLayout,
+) -> bool {
+    match format_host_layout(format) {
+        Some(host) => host == (base_format, layout),
+        None => format.base_format() == base_format,
+    }
+}
+
 /// Return the image view type most closely matching the image type.
 pub fn image_type_to_view_type(img_type: grr::ImageType) -> grr::ImageViewType {
     match img_type {
         grr::ImageType::D1 { layers, .. } if layers == 1 => grr::ImageViewType::D1,
         grr::ImageType::D1 { .. } => grr::ImageViewType::D1Array,
-        grr::ImageType::D2 { layers, .. } if layers == 1 => grr::ImageViewType::D2,
-        grr::ImageType::D2 { .. } => grr::ImageViewType::D2Array,
+        grr::ImageType::D2 { layers, samples, .. } => match (layers, samples > 1) {
+            (1, false) => grr::ImageViewType::D2,
+            (_, false) => grr::ImageViewType::D2Array,
+            (1, true) => grr::ImageViewType::D2Multisample,
+            (_, true) => grr::ImageViewType::D2MultisampleArray,
+        },
         grr::ImageType::D3 { .. } => grr::ImageViewType::D3,
     }
 }
@@ -118,3 +299,238 @@ pub fn image_type_to_full_extent(img_type: grr::ImageType) -> grr::Extent {
         depth: img_type.depth(),
     }
 }
+
+/// Return the number of array layers in an image type.
+pub fn image_type_num_layers(img_type: grr::ImageType) -> u32 {
+    match img_type {
+        grr::ImageType::D1 { layers, .. } => layers,
+        grr::ImageType::D2 { layers, .. } => layers,
+        grr::ImageType::D3 { .. } => 1,
+    }
+}
+
+/// Return the number of spatial dimensions of an image type.
+pub fn image_type_num_dims(img_type: grr::ImageType) -> usize {
+    match img_type {
+        grr::ImageType::D1 { .. } => 1,
+        grr::ImageType::D2 { .. } => 2,
+        grr::ImageType::D3 { .. } => 3,
+    }
+}
+
+/// Return the extent of a single mipmap level of an image type.
+///
+/// Each dimension is halved per level, and never drops below 1.
+pub fn mip_level_extent(img_type: grr::ImageType, level: u32) -> grr::Extent {
+    let full = image_type_to_full_extent(img_type);
+    let shrink = |x: u32| (x >> level).max(1);
+    grr::Extent {
+        width: shrink(full.width),
+        height: shrink(full.height),
+        depth: shrink(full.depth),
+    }
+}
+
+/// Return the number of samples per texel of an image type.
+pub fn image_type_samples(img_type: grr::ImageType) -> u32 {
+    match img_type {
+        grr::ImageType::D2 { samples, .. } => samples.max(1),
+        _ => 1,
+    }
+}
+
+/// Return the size of a block of the format, as `(width, height,
+/// bytes)`.
+///
+/// Uncompressed formats have 1x1 blocks. Returns `None` for formats
+/// whose size is not known.
+pub fn format_block_size(format: grr::Format) -> Option<(u32, u32, usize)> {
+    use grr::Format::*;
+    match format {
+        BC1_RGB_UNORM_BLOCK | BC1_RGB_SRGB_BLOCK | BC1_RGBA_UNORM_BLOCK | BC1_RGBA_SRGB_BLOCK
+        | BC4_UNORM_BLOCK | BC4_SNORM_BLOCK | ETC2_R8G8B8_UNORM_BLOCK | ETC2_R8G8B8_SRGB_BLOCK
+        | ETC2_R8G8B8A1_UNORM_BLOCK | ETC2_R8G8B8A1_SRGB_BLOCK | EAC_R11_UNORM_BLOCK
+        | EAC_R11_SNORM_BLOCK => return Some((4, 4, 8)),
+        BC2_UNORM_BLOCK | BC2_SRGB_BLOCK | BC3_UNORM_BLOCK | BC3_SRGB_BLOCK | BC5_UNORM_BLOCK
+        | BC5_SNORM_BLOCK | BC6H_UFLOAT_BLOCK | BC6H_SFLOAT_BLOCK | BC7_UNORM_BLOCK
+        | BC7_SRGB_BLOCK | ETC2_R8G8B8A8_UNORM_BLOCK | ETC2_R8G8B8A8_SRGB_BLOCK
+        | EAC_R11G11_UNORM_BLOCK | EAC_R11G11_SNORM_BLOCK => return Some((4, 4, 16)),
+        _ => {}
+    }
+
+    let bytes = match format {
+        R8_UNORM | R8_SNORM | R8_UINT | R8_SINT | S8_UINT => 1,
+        R8G8_UNORM | R8G8_SNORM | R8G8_UINT | R8G8_SINT => 2,
+        R16_UNORM | R16_SNORM | R16_UINT | R16_SINT | R16_SFLOAT | D16_UNORM => 2,
+        R8G8B8_UNORM | R8G8B8_SNORM | R8G8B8_UINT | R8G8B8_SINT | R8G8B8_SRGB => 3,
+        R8G8B8A8_UNORM | R8G8B8A8_SNORM | R8G8B8A8_UINT | R8G8B8A8_SINT | R8G8B8A8_SRGB => 4,
+        B8G8R8A8_UNORM | B8G8R8A8_SRGB => 4,
+        R16G16_UNORM | R16G16_SNORM | R16G16_UINT | R16G16_SINT | R16G16_SFLOAT => 4,
+        R32_UINT | R32_SINT | R32_SFLOAT | D32_SFLOAT => 4,
+        X8_D24_UNORM_PACK32 | D24_UNORM_S8_UINT => 4,
+        B10G11R11_UFLOAT_PACK32 | E5B9G9R9_UFLOAT_PACK32 => 4,
+        R16G16B16_UNORM | R16G16B16_SNORM | R16G16B16_UINT | R16G16B16_SINT => 6,
+        R16G16B16_SFLOAT => 6,
+        R16G16B16A16_UNORM | R16G16B16A16_SNORM | R16G16B16A16_UINT => 8,
+        R16G16B16A16_SINT | R16G16B16A16_SFLOAT => 8,
+        R32G32_UINT | R32G32_SINT | R32G32_SFLOAT | D32_SFLOAT_S8_UINT => 8,
+        R32G32B32_UINT | R32G32B32_SINT | R32G32B32_SFLOAT => 12,
+        R32G32B32A32_UINT | R32G32B32A32_SINT | R32G32B32A32_SFLOAT => 16,
+        _ => return None,
+    };
+    Some((1, 1, bytes))
+}
+
+/// Return true if the format holds unnormalized integers, which can
+/// only be read back as integers.
+pub fn format_is_integer(format: grr::Format) -> bool {
+    use grr::Format::*;
+    matches!(
+        format,
+        R8_UINT
+            | R8_SINT
+            | R8G8_UINT
+            | R8G8_SINT
+            | R8G8B8_UINT
+            | R8G8B8_SINT
+            | R8G8B8A8_UINT
+            | R8G8B8A8_SINT
+            | R16_UINT
+            | R16_SINT
+            | R16G16_UINT
+            | R16G16_SINT
+            | R16G16B16_UINT
+            | R16G16B16_SINT
+            | R16G16B16A16_UINT
+            | R16G16B16A16_SINT
+            | R32_UINT
+            | R32_SINT
+            | R32G32_UINT
+            | R32G32_SINT
+            | R32G32B32_UINT
+            | R32G32B32_SINT
+            | R32G32B32A32_UINT
+            | R32G32B32A32_SINT
+    )
+}
+
+/// Estimate the number of bytes used by an image with a full mip
+/// chain of `num_mipmap_levels` levels.
+///
+/// Returns `None` if the size of the format is not known, or the
+/// size does not fit in `usize`.
+pub fn image_memory_size(
+    img_type: grr::ImageType,
+    format: grr::Format,
+    num_mipmap_levels: u32,
+) -> Option<usize> {
+    let (bw, bh, block_bytes) = format_block_size(format)?;
+    let mut per_layer = 0usize;
+    for level in 0..num_mipmap_levels {
+        let e = mip_level_extent(img_type, level);
+        let blocks_x = ((e.width + bw - 1) / bw) as usize;
+        let blocks_y = ((e.height + bh - 1) / bh) as usize;
+        let level_bytes = blocks_x
+            .checked_mul(blocks_y)?
+            .checked_mul(e.depth as usize)?
+            .checked_mul(block_bytes)?;
+        per_layer = per_layer.checked_add(level_bytes)?;
+    }
+
+    per_layer
+        .checked_mul(image_type_num_layers(img_type) as usize)?
+        .checked_mul(image_type_samples(img_type) as usize)
+}
+
+/// Return true if an image of `img_type` can be viewed as
+/// `view_type`, when `num_layers` of its layers are viewed.
+pub fn view_type_compatible(
+    img_type: grr::ImageType,
+    view_type: grr::ImageViewType,
+    num_layers: u32,
+) -> bool {
+    use grr::ImageViewType as V;
+    match img_type {
+        grr::ImageType::D1 { .. } => match view_type {
+            V::D1 => num_layers == 1,
+            V::D1Array => true,
+            _ => false,
+        },
+        grr::ImageType::D2 {
+            width,
+            height,
+            samples,
+            ..
+        } => match view_type {
+            V::D2 => samples <= 1 && num_layers == 1,
+            V::D2Array => samples <= 1,
+            V::D2Multisample => samples > 1 && num_layers == 1,
+            V::D2MultisampleArray => samples > 1,
+            V::Cube => samples <= 1 && width == height && num_layers == 6,
+            V::CubeArray => samples <= 1 && width == height && num_layers % 6 == 0,
+            _ => false,
+        },
+        grr::ImageType::D3 { .. } => match view_type {
+            V::D3 => num_layers == 1,
+            _ => false,
+        },
+    }
+}
+
+/// Return the sRGB counterpart of a UNORM format, or vice-versa.
+pub fn srgb_pair(format: grr::Format) -> Option<grr::Format> {
+    use grr::Format::*;
+    let pairs = [
+        (R8G8B8_UNORM, R8G8B8_SRGB),
+        (R8G8B8A8_UNORM, R8G8B8A8_SRGB),
+        (B8G8R8A8_UNORM, B8G8R8A8_SRGB),
+        (BC1_RGB_UNORM_BLOCK, BC1_RGB_SRGB_BLOCK),
+        (BC1_RGBA_UNORM_BLOCK, BC1_RGBA_SRGB_BLOCK),
+        (BC2_UNORM_BLOCK, BC2_SRGB_BLOCK),
+        (BC3_UNORM_BLOCK, BC3_SRGB_BLOCK),
+        (BC7_UNORM_BLOCK, BC7_SRGB_BLOCK),
+        (ETC2_R8G8B8_UNORM_BLOCK, ETC2_R8G8B8_SRGB_BLOCK),
+        (ETC2_R8G8B8A1_UNORM_BLOCK, ETC2_R8G8B8A1_SRGB_BLOCK),
+        (ETC2_R8G8B8A8_UNORM_BLOCK, ETC2_R8G8B8A8_SRGB_BLOCK),
+    ];
+    pairs.iter().find_map(|&(unorm, srgb)| {
+        if format == unorm {
+            Some(srgb)
+        } else if format == srgb {
+            Some(unorm)
+        } else {
+            None
+        }
+    })
+}
+
+/// Return true if an image of `format` can be viewed as `view_format`.
+///
+/// Uncompressed color formats are compatible when their texels have
+/// the same size. Compressed and depth/stencil formats are only
+/// compatible with themselves and their sRGB counterpart. Formats of
+/// unknown size are only compatible with themselves.
+pub fn view_format_compatible(format: grr::Format, view_format: grr::Format) -> bool {
+    if format == view_format || srgb_pair(format) == Some(view_format) {
+        return true;
+    }
+
+    let is_color = |f: grr::Format| match f.base_format() {
+        grr::BaseFormat::Depth | grr::BaseFormat::DepthStencil | grr::BaseFormat::Stencil => false,
+        _ => true,
+    };
+    let (bw, bh, bytes) = match format_block_size(format) {
+        Some(size) => size,
+        None => return false,
+    };
+    let (vbw, vbh, vbytes) = match format_block_size(view_format) {
+        Some(size) => size,
+        None => return false,
+    };
+
+    is_color(format)
+        && is_color(view_format)
+        && (bw, bh) == (1, 1)
+        && (vbw, vbh) == (1, 1)
+        && bytes == vbytes
+}
@@ -1,5 +1,11 @@
 //! Manager for images, image views, and samplers
 use crate::image_format::*;
+use crate::raw_gl::{self, require_gl};
+use crate::screenshot::{self, ImageFileFormat, ToneMapping};
+use crate::texture_container::{self, CompressionSupport, ContainerError, TextureContainer};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use slotmap::{new_key_type, DenseSlotMap};
 use thiserror::Error;
 
@@ -17,29 +23,41 @@ struct Image {
     image_type: grr::ImageType,
     num_mipmap_levels: u32,
     format: grr::Format,
+
+    /// File the image was loaded from, if any.
+    source: Option<ImageSource>,
+
+    /// Modification time of the source file when it was last loaded.
+    modified: Option<SystemTime>,
 }
 
 impl Image {
     pub fn handle(&self) -> grr::Image {
         self.handle
     }
+
+    fn memory_size(&self) -> Option<usize> {
+        image_memory_size(self.image_type, self.format, self.num_mipmap_levels)
+    }
+}
+
+/// New storage and views for an image, created but not yet swapped
+/// in. Must be passed to `ImageManager::commit_storage` or
+/// `ImageManager::discard_storage`.
+pub(crate) struct PendingStorage {
+    image_id: ImageId,
+    image: Image,
+    views: Vec<(ImageViewId, ImageView)>,
 }
 
 /// Internal structure information for texture that specifically
 /// represents an image view.
 struct ImageView {
     handle: grr::ImageView,
-
-    #[allow(unused)]
     orig_handle: ImageId,
-
-    #[allow(unused)]
     image_view_type: grr::ImageViewType,
-    #[allow(unused)]
-    num_layers: u32,
-    #[allow(unused)]
-    num_mipmap_levels: u32,
-    #[allow(unused)]
+    levels: Range<u32>,
+    layers: Range<u32>,
     format: grr::Format,
 }
 
@@ -54,18 +72,45 @@ impl ImageView {
 pub enum Error {
     /// Internal `grr` error
     GrrError(#[from] grr::Error),
+    /// Error encoding or writing an image file
+    ImageError(#[from] image::ImageError),
+    /// Error reading a KTX2 or DDS container
+    ContainerError(#[from] ContainerError),
+    /// Raw GL function used before being loaded
+    GlNotLoaded(#[from] raw_gl::NotLoaded),
     MissingImageId(ImageId),
     BadDataLayout,
     ImproperDataFormat,
+    InvalidLevel(u32),
+    InvalidLayer(u32),
+    RegionOutOfBounds,
+    NotFileBacked(ImageId),
+    InvalidLevelRange(Range<u32>),
+    InvalidLayerRange(Range<u32>),
+    IncompatibleViewType(grr::ImageViewType),
+    IncompatibleViewFormat(grr::Format),
+    UnsavableFormat(grr::Format),
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::GrrError(e) => write!(f, "GrrError: {}", e),
+            Error::ImageError(e) => write!(f, "ImageError: {}", e),
+            Error::ContainerError(e) => write!(f, "ContainerError: {}", e),
+            Error::GlNotLoaded(e) => write!(f, "GlNotLoaded: {}", e),
             Error::MissingImageId(_) => write!(f, "MisisngImageId"),
             Error::BadDataLayout => write!(f, "BadDataLayout"),
             Error::ImproperDataFormat => write!(f, "ImproperDataFormat"),
+            Error::InvalidLevel(l) => write!(f, "InvalidLevel({})", l),
+            Error::InvalidLayer(l) => write!(f, "InvalidLayer({})", l),
+            Error::RegionOutOfBounds => write!(f, "RegionOutOfBounds"),
+            Error::NotFileBacked(_) => write!(f, "NotFileBacked"),
+            Error::InvalidLevelRange(r) => write!(f, "InvalidLevelRange({:?})", r),
+            Error::InvalidLayerRange(r) => write!(f, "InvalidLayerRange({:?})", r),
+            Error::IncompatibleViewType(t) => write!(f, "IncompatibleViewType({:?})", t),
+            Error::IncompatibleViewFormat(fmt) => write!(f, "IncompatibleViewFormat({:?})", fmt),
+            Error::UnsavableFormat(fmt) => write!(f, "UnsavableFormat({:?})", fmt),
         }
     }
 }
@@ -86,6 +131,214 @@ impl From<ImageId> for ImageOrViewId {
     }
 }
 
+/// A rectangular region within a single mipmap level and layer of an image.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageRegion {
+    pub level: u32,
+    pub layer: u32,
+    pub offset: grr::Offset,
+
+    /// Size of the region. `None` extends the region to the end of
+    /// the level.
+    pub extent: Option<grr::Extent>,
+}
+
+impl ImageRegion {
+    /// The entirety of a single mipmap level and layer.
+    pub fn whole(level: u32, layer: u32) -> ImageRegion {
+        ImageRegion {
+            level,
+            layer,
+            offset: grr::Offset::ORIGIN,
+            extent: None,
+        }
+    }
+}
+
+impl Default for ImageRegion {
+    fn default() -> ImageRegion {
+        ImageRegion::whole(0, 0)
+    }
+}
+
+/// Description of an image view: the levels and layers it covers,
+/// how they are interpreted, and the format they are viewed as.
+///
+/// Unset properties default to those of the whole image.
+#[derive(Debug, Clone, Default)]
+pub struct ImageViewDesc {
+    levels: Option<Range<u32>>,
+    layers: Option<Range<u32>>,
+    view_type: Option<grr::ImageViewType>,
+    format: Option<grr::Format>,
+}
+
+impl ImageViewDesc {
+    pub fn new() -> ImageViewDesc {
+        ImageViewDesc::default()
+    }
+
+    /// View a range of mipmap levels.
+    pub fn levels(self, levels: Range<u32>) -> ImageViewDesc {
+        ImageViewDesc {
+            levels: Some(levels),
+            ..self
+        }
+    }
+
+    /// View a range of array layers.
+    pub fn layers(self, layers: Range<u32>) -> ImageViewDesc {
+        ImageViewDesc {
+            layers: Some(layers),
+            ..self
+        }
+    }
+
+    /// View a single array layer.
+    pub fn layer(self, layer: u32) -> ImageViewDesc {
+        self.layers(layer..layer + 1)
+    }
+
+    pub fn view_type(self, view_type: grr::ImageViewType) -> ImageViewDesc {
+        ImageViewDesc {
+            view_type: Some(view_type),
+            ..self
+        }
+    }
+
+    /// Reinterpret the texels with another format of the same size,
+    /// e.g. UNORM as SRGB.
+    pub fn format(self, format: grr::Format) -> ImageViewDesc {
+        ImageViewDesc {
+            format: Some(format),
+            ..self
+        }
+    }
+}
+
+/// Options for loading an image file through the `image` crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageLoadOptions {
+    /// Store the image in an sRGB format.
+    pub srgb: bool,
+
+    /// Allocate and generate a full mip chain.
+    pub generate_mipmaps: bool,
+
+    /// Flip the file vertically, so that its bottom row is stored
+    /// first, matching OpenGL's bottom-up texture coordinates. When
+    /// unset, the top row of the file is stored first.
+    pub flip_vertically: bool,
+}
+
+impl Default for ImageLoadOptions {
+    fn default() -> ImageLoadOptions {
+        ImageLoadOptions {
+            srgb: true,
+            generate_mipmaps: true,
+            flip_vertically: true,
+        }
+    }
+}
+
+/// Where a file-backed image was loaded from, and how.
+#[derive(Debug, Clone)]
+enum ImageSource {
+    File {
+        path: PathBuf,
+        options: ImageLoadOptions,
+    },
+    Container {
+        path: PathBuf,
+        support: CompressionSupport,
+    },
+}
+
+impl ImageSource {
+    fn path(&self) -> &Path {
+        match self {
+            ImageSource::File { path, .. } => path,
+            ImageSource::Container { path, .. } => path,
+        }
+    }
+}
+
+/// Return the modification time of a file, if available.
+fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Properties of a managed image.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageInfo {
+    pub image_type: grr::ImageType,
+    pub format: grr::Format,
+
+    /// Extent of mipmap level 0.
+    pub extent: grr::Extent,
+    pub num_layers: u32,
+    pub num_mipmap_levels: u32,
+    pub samples: u32,
+
+    /// Number of views created over this image.
+    pub num_views: usize,
+
+    /// Estimated size of the image storage, in bytes, or `None` if
+    /// the size of the format is not known.
+    pub memory_size: Option<usize>,
+}
+
+/// Summary of the images and views held by an `ImageManager`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MemoryUsage {
+    pub num_images: usize,
+    pub num_views: usize,
+
+    /// Estimated size of all image storage, in bytes. Images of
+    /// unknown size are not counted.
+    pub total_bytes: usize,
+}
+
+/// Options for writing a managed image to disk.
+#[derive(Debug, Clone, Copy)]
+pub struct SaveImageOptions {
+    pub level: u32,
+
+    /// Array layer to save. For 3D images, this selects the depth
+    /// slice instead.
+    pub layer: u32,
+
+    pub file_format: ImageFileFormat,
+
+    /// Applied to float data written to PNG.
+    pub tone_mapping: ToneMapping,
+
+    /// Images are stored bottom row first; set this to write the
+    /// top row first.
+    pub flip_vertically: bool,
+}
+
+impl Default for SaveImageOptions {
+    fn default() -> SaveImageOptions {
+        SaveImageOptions {
+            level: 0,
+            layer: 0,
+            file_format: ImageFileFormat::Png,
+            tone_mapping: ToneMapping::Clamp,
+            flip_vertically: false,
+        }
+    }
+}
+
+/// Return the extent covered by an array, failing if a dimension
+/// does not fit in a `u32`.
+fn ndarray_extent<A, D: TextureDim>(data: &ndarray::Array<A, D>) -> Result<grr::Extent, Error> {
+    if data.shape().iter().any(|&n| n > u32::MAX as usize) {
+        return Err(Error::RegionOutOfBounds);
+    }
+    Ok(image_type_to_full_extent(data.raw_dim().image_type()))
+}
+
 /// Create and bind images, with caching for image properties.
 pub struct ImageManager<'d> {
     images: DenseSlotMap<ImageId, Image>,
@@ -102,6 +355,11 @@ impl<'d> ImageManager<'d> {
         }
     }
 
+    /// Return the device that images are created on.
+    pub fn device(&self) -> &'d grr::Device {
+        self.device
+    }
+
     /// Create a new image with the specified storage format and type.
     pub fn create_image(
         &mut self,
@@ -119,6 +377,8 @@ impl<'d> ImageManager<'d> {
             image_type,
             format,
             num_mipmap_levels,
+            source: None,
+            modified: None,
         }))
     }
 
@@ -177,55 +437,838 @@ impl<'d> ImageManager<'d> {
         Ok(handle)
     }
 
-    /// Create a new image view, using the full image.
-    pub fn create_image_view_whole(&mut self, image_id: ImageId) -> Result<ImageViewId, Error> {
-        let image = match self.images.get(image_id) {
-            Some(img) => img,
+    /// Create an image from explicit data for every mipmap level,
+    /// starting with level 0.
+    pub fn create_image_from_mip_chain<
+        PC: TexelBaseType,
+        D: TextureDim,
+        CD: TextureComponentDim,
+    >(
+        &mut self,
+        levels: &[ndarray::Array<nalgebra::VectorN<PC, CD>, D>],
+        format: grr::Format,
+    ) -> Result<ImageId, Error>
+    where
+        nalgebra::DefaultAllocator: nalgebra::base::allocator::Allocator<PC, CD>,
+    {
+        let image_type = levels
+            .first()
+            .ok_or(Error::BadDataLayout)?
+            .raw_dim()
+            .image_type();
+        let image_id = self.create_image(image_type, format, levels.len() as u32)?;
+
+        for (level, data) in levels.iter().enumerate() {
+            let res = self.update_region_from_ndarray(
+                image_id,
+                &ImageRegion::whole(level as u32, 0),
+                data,
+                false,
+            );
+            if let Err(e) = res {
+                self.delete_image(image_id);
+                return Err(e);
+            }
+        }
+
+        Ok(image_id)
+    }
+
+    /// Create an image from an array of texels, with the format
+    /// deduced from the texel type.
+    pub fn create_image_from_texels<T: TexelType, D: TextureDim>(
+        &mut self,
+        data: &ndarray::Array<T, D>,
+        num_mip_map_levels: u32,
+        gen_mipmaps: bool,
+    ) -> Result<ImageId, Error> {
+        let format = T::format().ok_or(Error::ImproperDataFormat)?;
+        let image_id = self.create_image(data.raw_dim().image_type(), format, num_mip_map_levels)?;
+
+        let res =
+            self.update_region_from_texels(image_id, &ImageRegion::whole(0, 0), data, gen_mipmaps);
+        if let Err(e) = res {
+            self.delete_image(image_id);
+            return Err(e);
+        }
+
+        Ok(image_id)
+    }
+
+    /// Load a KTX2 or DDS texture container, including its mip
+    /// chain, array layers and cube faces.
+    ///
+    /// Returns the image and a view over all of it; cube maps are
+    /// viewed as cubes (or cube arrays).
+    pub fn load_texture_container<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        support: CompressionSupport,
+    ) -> Result<(ImageId, ImageViewId), Error> {
+        let path = path.as_ref();
+        let modified = file_modified(path);
+        let bytes = std::fs::read(path).map_err(ContainerError::from)?;
+        let container = TextureContainer::parse(&bytes)?;
+        container.check_support(support)?;
+
+        let (image_id, view_id) = self.create_image_from_container(&container)?;
+        let image = &mut self.images[image_id];
+        image.source = Some(ImageSource::Container {
+            path: path.to_owned(),
+            support,
+        });
+        image.modified = modified;
+
+        Ok((image_id, view_id))
+    }
+
+    /// Load an image file (PNG, JPEG, etc.) as an 8-bit RGBA image.
+    ///
+    /// The path and options are remembered, so that the image can be
+    /// reloaded with `reload_image`.
+    pub fn load_image<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: ImageLoadOptions,
+    ) -> Result<ImageId, Error> {
+        let path = path.as_ref();
+        let modified = file_modified(path);
+        let image_id = self.create_image_from_file(path, options)?;
+
+        let image = &mut self.images[image_id];
+        image.source = Some(ImageSource::File {
+            path: path.to_owned(),
+            options,
+        });
+        image.modified = modified;
+
+        Ok(image_id)
+    }
+
+    /// Decode an image file and upload it into a new image.
+    fn create_image_from_file(
+        &mut self,
+        path: &Path,
+        options: ImageLoadOptions,
+    ) -> Result<ImageId, Error> {
+        let mut img = image::open(path)?.to_rgba8();
+        if options.flip_vertically {
+            image::imageops::flip_vertical_in_place(&mut img);
+        }
+        let (width, height) = img.dimensions();
+
+        let image_type = grr::ImageType::D2 {
+            width,
+            height,
+            layers: 1,
+            samples: 1,
+        };
+        let format = if options.srgb {
+            grr::Format::R8G8B8A8_SRGB
+        } else {
+            grr::Format::R8G8B8A8_UNORM
+        };
+        let num_levels = if options.generate_mipmaps {
+            32 - width.max(height).leading_zeros()
+        } else {
+            1
+        };
+
+        let image_id = self.create_image(image_type, format, num_levels)?;
+        self.update_region(
+            image_id,
+            &ImageRegion::default(),
+            grr::BaseFormat::RGBA,
+            img.as_raw(),
+            options.generate_mipmaps,
+        )?;
+
+        Ok(image_id)
+    }
+
+    /// Re-decode a file-backed image from its source, replacing the
+    /// image storage in place.
+    ///
+    /// The `ImageId` and the ids of all views over the image remain
+    /// valid; views are re-created over the new storage, so their raw
+    /// handles (and any bindings made with them) must be refreshed. On
+    /// failure, the image is left unchanged.
+    pub fn reload_image(&mut self, image_id: ImageId) -> Result<(), Error> {
+        let source = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?
+            .source
+            .clone()
+            .ok_or(Error::NotFileBacked(image_id))?;
+        let modified = file_modified(source.path());
+
+        let new_id = match &source {
+            ImageSource::File { path, options } => self.create_image_from_file(path, *options)?,
+            ImageSource::Container { path, support } => {
+                let bytes = std::fs::read(path).map_err(ContainerError::from)?;
+                let container = TextureContainer::parse(&bytes)?;
+                container.check_support(*support)?;
+                let (new_id, new_view) = self.create_image_from_container(&container)?;
+                self.delete_image_view(new_view);
+                new_id
+            }
+        };
+
+        self.replace_storage(image_id, new_id)?;
+        self.images[image_id].modified = modified;
+        Ok(())
+    }
+
+    /// Reload every file-backed image.
+    ///
+    /// With `only_modified`, only images whose source file has
+    /// changed since it was last loaded are reloaded. Returns the
+    /// images that failed to reload, which are left unchanged.
+    pub fn reload_all_images(&mut self, only_modified: bool) -> Vec<(ImageId, Error)> {
+        let to_reload: Vec<ImageId> = self
+            .images
+            .iter()
+            .filter_map(|(id, image)| {
+                let source = image.source.as_ref()?;
+                if only_modified && file_modified(source.path()) == image.modified {
+                    None
+                } else {
+                    Some(id)
+                }
+            })
+            .collect();
+
+        to_reload
+            .into_iter()
+            .filter_map(|id| self.reload_image(id).err().map(|e| (id, e)))
+            .collect()
+    }
+
+    /// Move the storage of image `src` into image `dst`, and delete
+    /// `src` along with the previous storage of `dst`.
+    ///
+    /// The views of `dst` are re-created over the new storage.
+    fn replace_storage(&mut self, dst: ImageId, src: ImageId) -> Result<(), Error> {
+        if !self.images.contains_key(dst) {
+            return Err(Error::MissingImageId(dst));
+        }
+        let src_views: Vec<_> = self.image_views(src).collect();
+        for v in src_views {
+            self.delete_image_view(v);
+        }
+        let new_image = self.images.remove(src).ok_or(Error::MissingImageId(src))?;
+
+        self.swap_storage(dst, new_image)
+    }
+
+    /// Replace the storage of an image with `new`, re-creating its
+    /// views over the new storage.
+    ///
+    /// Every view is created before anything is changed: on failure,
+    /// `new` is deleted and the image and its views are left as they
+    /// were. On success, the previous storage and views are deleted.
+    fn swap_storage(&mut self, image_id: ImageId, new: Image) -> Result<(), Error> {
+        let pending = self.prepare_storage(image_id, new)?;
+        self.commit_storage(pending);
+        Ok(())
+    }
+
+    /// Create the views of an image over the storage of `new`,
+    /// without swapping anything in. On failure, `new` is deleted.
+    fn prepare_storage(&self, image_id: ImageId, new: Image) -> Result<PendingStorage, Error> {
+        match self.create_replacement_views(image_id, &new) {
+            Ok(views) => Ok(PendingStorage {
+                image_id,
+                image: new,
+                views,
+            }),
+            Err(e) => {
+                unsafe {
+                    self.device.delete_image(new.handle);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Swap in storage created by `prepare_storage`, deleting the
+    /// previous storage and views.
+    pub(crate) fn commit_storage(&mut self, pending: PendingStorage) {
+        let PendingStorage {
+            image_id,
+            image: new,
+            views,
+        } = pending;
+        let image = match self.images.get_mut(image_id) {
+            Some(image) => image,
             None => {
-                return Err(Error::MissingImageId(image_id));
+                self.discard_storage(PendingStorage {
+                    image_id,
+                    image: new,
+                    views,
+                });
+                return;
             }
         };
+        let old_handle = std::mem::replace(&mut image.handle, new.handle);
+        image.image_type = new.image_type;
+        image.num_mipmap_levels = new.num_mipmap_levels;
+        image.format = new.format;
+
+        for (view_id, view) in views {
+            match self.views.get_mut(view_id) {
+                Some(slot) => {
+                    let old_view = std::mem::replace(slot, view);
+                    unsafe {
+                        self.device.delete_image_view(old_view.handle);
+                    }
+                }
+                None => unsafe {
+                    self.device.delete_image_view(view.handle);
+                },
+            }
+        }
+        unsafe {
+            self.device.delete_image(old_handle);
+        }
+    }
+
+    /// Delete storage created by `prepare_storage` without using it.
+    pub(crate) fn discard_storage(&self, pending: PendingStorage) {
+        unsafe {
+            for (_, view) in pending.views {
+                self.device.delete_image_view(view.handle);
+            }
+            self.device.delete_image(pending.image.handle);
+        }
+    }
+
+    /// Create a copy of every view of an image over the storage of
+    /// `new`.
+    ///
+    /// If any view cannot be created, those already created are
+    /// deleted.
+    fn create_replacement_views(
+        &self,
+        image_id: ImageId,
+        new: &Image,
+    ) -> Result<Vec<(ImageViewId, ImageView)>, Error> {
+        let old = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+
+        let mut created: Vec<(ImageViewId, ImageView)> = Vec::new();
+        for (view_id, view) in self.views.iter().filter(|(_, v)| v.orig_handle == image_id) {
+            match self.create_replacement_view(view, old, new) {
+                Ok(v) => created.push((view_id, v)),
+                Err(e) => {
+                    for (_, v) in created {
+                        unsafe {
+                            self.device.delete_image_view(v.handle);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
 
-        let image_view_type = image_type_to_view_type(image.image_type);
-        let num_layers = match image.image_type {
-            grr::ImageType::D1 { layers, .. } => layers,
-            grr::ImageType::D2 { layers, .. } => layers,
-            grr::ImageType::D3 { .. } => 1,
+        Ok(created)
+    }
+
+    /// Create a copy of `view` of image `old` over the storage of
+    /// `new`. Views that covered all of `old` cover all of `new`;
+    /// views over a sub-range must fit in `new`.
+    fn create_replacement_view(
+        &self,
+        view: &ImageView,
+        old: &Image,
+        new: &Image,
+    ) -> Result<ImageView, Error> {
+        let old_num_layers = image_type_num_layers(old.image_type);
+        let num_layers = image_type_num_layers(new.image_type);
+
+        let (levels, layers) =
+            if view.levels == (0..old.num_mipmap_levels) && view.layers == (0..old_num_layers) {
+                (0..new.num_mipmap_levels, 0..num_layers)
+            } else {
+                (view.levels.clone(), view.layers.clone())
+            };
+        if levels.end > new.num_mipmap_levels {
+            return Err(Error::InvalidLevelRange(levels));
+        }
+        if layers.end > num_layers {
+            return Err(Error::InvalidLayerRange(layers));
+        }
+        if !view_type_compatible(new.image_type, view.image_view_type, layers.len() as u32) {
+            return Err(Error::IncompatibleViewType(view.image_view_type));
+        }
+        let format = if view.format == old.format {
+            new.format
+        } else {
+            view.format
         };
 
         let sub_range = grr::SubresourceRange {
-            levels: 0..image.num_mipmap_levels,
-            layers: 0..num_layers,
+            levels: levels.clone(),
+            layers: layers.clone(),
+        };
+        let handle = unsafe {
+            self.device
+                .create_image_view(new.handle, view.image_view_type, format, sub_range)?
+        };
+
+        Ok(ImageView {
+            handle,
+            orig_handle: view.orig_handle,
+            image_view_type: view.image_view_type,
+            levels,
+            layers,
+            format,
+        })
+    }
+
+    /// Create an image from an already-parsed texture container.
+    pub fn create_image_from_container(
+        &mut self,
+        container: &TextureContainer,
+    ) -> Result<(ImageId, ImageViewId), Error> {
+        let num_levels = if container.generate_mipmaps {
+            let e = container.width.max(container.height).max(container.depth);
+            32 - e.leading_zeros()
+        } else {
+            container.levels.len() as u32
+        };
+
+        let image_id = self.create_image(container.image_type(), container.format, num_levels)?;
+        if let Err(e) = self.upload_container(image_id, container) {
+            self.delete_image(image_id);
+            return Err(e);
+        }
+
+        let view_type = match (container.is_cube(), container.num_layers) {
+            (true, 1) => grr::ImageViewType::Cube,
+            (true, _) => grr::ImageViewType::CubeArray,
+            _ => image_type_to_view_type(container.image_type()),
+        };
+        let view_id = match self.create_image_view_typed(image_id, view_type) {
+            Ok(view_id) => view_id,
+            Err(e) => {
+                self.delete_image(image_id);
+                return Err(e);
+            }
+        };
+
+        Ok((image_id, view_id))
+    }
+
+    /// Upload every level of a container into an existing image of
+    /// matching type and format.
+    fn upload_container(&self, image_id: ImageId, container: &TextureContainer) -> Result<(), Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+        let num_layers = image_type_num_layers(image.image_type);
+
+        for (level, data) in container.levels.iter().enumerate() {
+            let level = level as u32;
+            let extent = mip_level_extent(image.image_type, level);
+
+            // The upload reads a whole level from `data`.
+            if container.expected_level_size(level) != Some(data.len()) {
+                return Err(ContainerError::Malformed.into());
+            }
+
+            if texture_container::is_compressed(container.format) {
+                require_gl!(CompressedTextureSubImage2D, CompressedTextureSubImage3D)?;
+                let raw = grr::Object::handle(&image.handle());
+                unsafe {
+                    match image.image_type {
+                        grr::ImageType::D2 { layers: 1, .. } => gl::CompressedTextureSubImage2D(
+                            raw,
+                            level as _,
+                            0,
+                            0,
+                            extent.width as _,
+                            extent.height as _,
+                            container.format as u32,
+                            data.len() as _,
+                            data.as_ptr() as *const _,
+                        ),
+                        grr::ImageType::D2 { layers, .. } => gl::CompressedTextureSubImage3D(
+                            raw,
+                            level as _,
+                            0,
+                            0,
+                            0,
+                            extent.width as _,
+                            extent.height as _,
+                            layers as _,
+                            container.format as u32,
+                            data.len() as _,
+                            data.as_ptr() as *const _,
+                        ),
+                        grr::ImageType::D3 { .. } => gl::CompressedTextureSubImage3D(
+                            raw,
+                            level as _,
+                            0,
+                            0,
+                            0,
+                            extent.width as _,
+                            extent.height as _,
+                            extent.depth as _,
+                            container.format as u32,
+                            data.len() as _,
+                            data.as_ptr() as *const _,
+                        ),
+                        grr::ImageType::D1 { .. } => {
+                            return Err(ContainerError::UnsupportedFeature(
+                                "compressed 1D textures",
+                            )
+                            .into())
+                        }
+                    }
+                }
+            } else {
+                let (base_format, format_layout) = format_host_layout(container.format)
+                    .ok_or_else(|| {
+                        ContainerError::UnsupportedFormat(format!("{:?}", container.format))
+                    })?;
+                unsafe {
+                    self.device.copy_host_to_image(
+                        data,
+                        image.handle(),
+                        grr::HostImageCopy {
+                            host_layout: grr::MemoryLayout {
+                                base_format,
+                                format_layout,
+                                row_length: 0,
+                                image_height: 0,
+                                alignment: 1,
+                            },
+                            image_subresource: grr::SubresourceLayers {
+                                level,
+                                layers: 0..num_layers,
+                            },
+                            image_offset: grr::Offset::ORIGIN,
+                            image_extent: extent,
+                        },
+                    );
+                }
+            }
+        }
+
+        if container.generate_mipmaps {
+            unsafe {
+                self.device.generate_mipmaps(image.handle());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Create a new image view, using the full image.
+    pub fn create_image_view_whole(&mut self, image_id: ImageId) -> Result<ImageViewId, Error> {
+        self.create_image_view(image_id, &ImageViewDesc::new())
+    }
+
+    /// Create a view of the full image with a specific view type.
+    fn create_image_view_typed(
+        &mut self,
+        image_id: ImageId,
+        image_view_type: grr::ImageViewType,
+    ) -> Result<ImageViewId, Error> {
+        self.create_image_view(image_id, &ImageViewDesc::new().view_type(image_view_type))
+    }
+
+    /// Create a view over part of an image, possibly with a different
+    /// view type or a compatible format.
+    pub fn create_image_view(
+        &mut self,
+        image_id: ImageId,
+        desc: &ImageViewDesc,
+    ) -> Result<ImageViewId, Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+
+        let num_layers = image_type_num_layers(image.image_type);
+        let levels = desc.levels.clone().unwrap_or(0..image.num_mipmap_levels);
+        let layers = desc.layers.clone().unwrap_or(0..num_layers);
+        let format = desc.format.unwrap_or(image.format);
+
+        if levels.start >= levels.end || levels.end > image.num_mipmap_levels {
+            return Err(Error::InvalidLevelRange(levels));
+        }
+        if layers.start >= layers.end || layers.end > num_layers {
+            return Err(Error::InvalidLayerRange(layers));
+        }
+
+        // Without an explicit type, pick the closest match to the
+        // number of layers viewed.
+        let image_view_type = match desc.view_type {
+            Some(t) => t,
+            None => match image.image_type {
+                grr::ImageType::D1 { .. } if layers.len() == 1 => grr::ImageViewType::D1,
+                grr::ImageType::D2 { samples, .. } if layers.len() == 1 => {
+                    if samples > 1 {
+                        grr::ImageViewType::D2Multisample
+                    } else {
+                        grr::ImageViewType::D2
+                    }
+                }
+                _ => image_type_to_view_type(image.image_type),
+            },
+        };
+
+        if !view_type_compatible(image.image_type, image_view_type, layers.len() as u32) {
+            return Err(Error::IncompatibleViewType(image_view_type));
+        }
+        if !view_format_compatible(image.format, format) {
+            return Err(Error::IncompatibleViewFormat(format));
+        }
+
+        let sub_range = grr::SubresourceRange {
+            levels: levels.clone(),
+            layers: layers.clone(),
         };
 
         let handle = unsafe {
             self.device
-                .create_image_view(image.handle, image_view_type, image.format, sub_range)?
+                .create_image_view(image.handle, image_view_type, format, sub_range)?
         };
 
         Ok(self.views.insert(ImageView {
             handle,
             orig_handle: image_id,
             image_view_type,
-            num_layers,
+            levels,
+            layers,
+            format,
+        }))
+    }
+
+    /// Check that `region` lies within `image`, and return the
+    /// resolved extent of the region.
+    fn resolve_region(image: &Image, region: &ImageRegion) -> Result<grr::Extent, Error> {
+        if region.level >= image.num_mipmap_levels {
+            return Err(Error::InvalidLevel(region.level));
+        }
+        if region.layer >= image_type_num_layers(image.image_type) {
+            return Err(Error::InvalidLayer(region.layer));
+        }
+
+        let level_extent = mip_level_extent(image.image_type, region.level);
+        let o = region.offset;
+        if o.x < 0 || o.y < 0 || o.z < 0 {
+            return Err(Error::RegionOutOfBounds);
+        }
+        let (x, y, z) = (o.x as u32, o.y as u32, o.z as u32);
+        if x >= level_extent.width || y >= level_extent.height || z >= level_extent.depth {
+            return Err(Error::RegionOutOfBounds);
+        }
+
+        let extent = region.extent.unwrap_or(grr::Extent {
+            width: level_extent.width - x,
+            height: level_extent.height - y,
+            depth: level_extent.depth - z,
+        });
+        let fits = |start: u32, size: u32, end: u32| {
+            start.checked_add(size).map_or(false, |e| e <= end)
+        };
+        if !fits(x, extent.width, level_extent.width)
+            || !fits(y, extent.height, level_extent.height)
+            || !fits(z, extent.depth, level_extent.depth)
+        {
+            return Err(Error::RegionOutOfBounds);
+        }
+
+        Ok(extent)
+    }
+
+    /// Return the number of values needed to hold `extent` texels
+    /// with `base_format` components each.
+    fn region_num_values(extent: grr::Extent, base_format: grr::BaseFormat) -> Result<usize, Error> {
+        (extent.width as usize)
+            .checked_mul(extent.height as usize)
+            .and_then(|n| n.checked_mul(extent.depth as usize))
+            .and_then(|n| n.checked_mul(base_format_num_components(base_format)))
+            .ok_or(Error::RegionOutOfBounds)
+    }
+
+    /// Read a region of the image into a packed vector, with
+    /// `base_format` components per texel.
+    fn read_region_vec<T: TexelBaseType>(
+        &self,
+        image_id: ImageId,
+        base_format: grr::BaseFormat,
+        region: &ImageRegion,
+    ) -> Result<(Vec<T>, grr::Extent), Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+
+        let extent = Self::resolve_region(image, region)?;
+
+        let num_values = Self::region_num_values(extent, base_format)?;
+        let mut texture_data: Vec<T> = vec![T::zero(); num_values];
+
+        let mem_layout = grr::MemoryLayout {
+            base_format,
+            format_layout: T::layout,
+            row_length: 0,
+            image_height: 0,
+            alignment: 1,
+        };
+
+        unsafe {
+            self.device.copy_image_to_host(
+                image.handle(),
+                &mut texture_data,
+                grr::HostImageCopy {
+                    host_layout: mem_layout,
+                    image_subresource: grr::SubresourceLayers {
+                        level: region.level,
+                        layers: region.layer..region.layer + 1,
+                    },
+                    image_offset: region.offset,
+                    image_extent: extent,
+                },
+            );
+        }
+
+        Ok((texture_data, extent))
+    }
+
+    /// Replace the storage of an image with a newly-allocated image
+    /// of `image_type`, keeping the same `ImageId`.
+    ///
+    /// The format and number of mipmap levels are unchanged, and the
+    /// contents are undefined. Existing views of the image are
+    /// re-created over the new storage, so their `ImageViewId`s stay
+    /// valid, but their raw handles change. On failure, the image and
+    /// its views are left unchanged.
+    pub fn reallocate_image(
+        &mut self,
+        image_id: ImageId,
+        image_type: grr::ImageType,
+    ) -> Result<(), Error> {
+        let pending = self.prepare_reallocation(image_id, image_type)?;
+        self.commit_storage(pending);
+        Ok(())
+    }
+
+    /// Allocate the storage and views for `reallocate_image` without
+    /// swapping them in, so several images can be reallocated
+    /// together with `commit_storage` or `discard_storage`.
+    pub(crate) fn prepare_reallocation(
+        &self,
+        image_id: ImageId,
+        image_type: grr::ImageType,
+    ) -> Result<PendingStorage, Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+
+        let handle = unsafe {
+            self.device
+                .create_image(image_type, image.format, image.num_mipmap_levels)?
+        };
+        let new = Image {
+            handle,
+            image_type,
             num_mipmap_levels: image.num_mipmap_levels,
             format: image.format,
-        }))
+            source: None,
+            modified: None,
+        };
+
+        self.prepare_storage(image_id, new)
     }
 
     /// Return the texture as a packed vector.
+    ///
+    /// Reads the entirety of level 0, layer 0, in the image's own
+    /// base format.
     pub fn get_texture_vec<T: TexelBaseType>(&self, image_id: ImageId) -> Result<Vec<T>, Error> {
         let image = self
             .images
             .get(image_id)
             .ok_or(Error::MissingImageId(image_id))?;
 
-        let num_texels = image.image_type.num_texels() * image.format.num_components() as usize;
-        let mut texture_data: Vec<T> = Vec::with_capacity(num_texels);
-        texture_data.resize(num_texels, T::zero());
+        self.read_region_vec(image_id, image.format.base_format(), &ImageRegion::default())
+            .map(|(data, _)| data)
+    }
+
+    /// Read a region of an image back to the host.
+    ///
+    /// The result is shaped the same way as arrays passed to
+    /// `create_image_from_ndarray`, with `CD` components per
+    /// texel. The component count does not need to match the image's
+    /// format.
+    pub fn read_image_region<PC: TexelBaseType, D: TextureDim, CD: TextureComponentDim>(
+        &self,
+        image_id: ImageId,
+        region: &ImageRegion,
+    ) -> Result<ndarray::Array<nalgebra::VectorN<PC, CD>, D>, Error>
+    where
+        nalgebra::DefaultAllocator: nalgebra::base::allocator::Allocator<PC, CD>,
+    {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+        if D::NDIM != Some(image_type_num_dims(image.image_type)) {
+            return Err(Error::BadDataLayout);
+        }
+
+        let (data, extent) = self.read_region_vec::<PC>(image_id, CD::base_format, region)?;
+        let texels: Vec<_> = data
+            .chunks_exact(CD::dim())
+            .map(nalgebra::VectorN::<PC, CD>::from_column_slice)
+            .collect();
+
+        ndarray::Array::from_shape_vec(D::from_extent(extent), texels)
+            .map_err(|_| Error::BadDataLayout)
+    }
+
+    /// Upload packed host data into a region of an existing image.
+    ///
+    /// `base_format` describes the components of each texel in
+    /// `data`. Both it and the component type `T` must match the
+    /// image's format. If
+    /// `gen_mipmaps` is set, the remaining levels of the image are
+    /// regenerated from level 0 afterwards.
+    pub fn update_region<T: TexelBaseType>(
+        &self,
+        image_id: ImageId,
+        region: &ImageRegion,
+        base_format: grr::BaseFormat,
+        data: &[T],
+        gen_mipmaps: bool,
+    ) -> Result<(), Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+
+        if !host_layout_compatible(image.format, base_format, T::layout) {
+            return Err(Error::ImproperDataFormat);
+        }
+
+        let extent = Self::resolve_region(image, region)?;
+        let num_values = Self::region_num_values(extent, base_format)?;
+        if data.len() != num_values {
+            return Err(Error::BadDataLayout);
+        }
 
         let mem_layout = grr::MemoryLayout {
-            base_format: image.format.base_format(),
+            base_format,
             format_layout: T::layout,
             row_length: 0,
             image_height: 0,
@@ -233,22 +1276,215 @@ impl<'d> ImageManager<'d> {
         };
 
         unsafe {
-            self.device.copy_image_to_host(
+            self.device.copy_host_to_image(
+                data,
                 image.handle(),
-                &mut texture_data,
                 grr::HostImageCopy {
                     host_layout: mem_layout,
                     image_subresource: grr::SubresourceLayers {
-                        level: 0,
-                        layers: 0..1,
+                        level: region.level,
+                        layers: region.layer..region.layer + 1,
                     },
-                    image_offset: grr::Offset::ORIGIN,
-                    image_extent: image.image_type.full_extent(),
+                    image_offset: region.offset,
+                    image_extent: extent,
                 },
             );
+
+            if gen_mipmaps {
+                self.device.generate_mipmaps(image.handle());
+            }
         }
 
-        Ok(texture_data)
+        Ok(())
+    }
+
+    /// Upload an array into a region of an existing image.
+    ///
+    /// The extent of the region is taken from the shape of `data`;
+    /// any extent in `region` is ignored.
+    pub fn update_region_from_ndarray<PC: TexelBaseType, D: TextureDim, CD: TextureComponentDim>(
+        &self,
+        image_id: ImageId,
+        region: &ImageRegion,
+        data: &ndarray::Array<nalgebra::VectorN<PC, CD>, D>,
+        gen_mipmaps: bool,
+    ) -> Result<(), Error>
+    where
+        nalgebra::DefaultAllocator: nalgebra::base::allocator::Allocator<PC, CD>,
+    {
+        let d = data.as_slice().ok_or(Error::ImproperDataFormat)?;
+        let region = ImageRegion {
+            extent: Some(ndarray_extent(data)?),
+            ..*region
+        };
+
+        let values: Vec<PC> = d.iter().flat_map(|v| v.as_slice().iter().copied()).collect();
+
+        self.update_region(image_id, &region, CD::base_format, &values, gen_mipmaps)
+    }
+
+    /// Upload an array of texels into a region of an existing image.
+    ///
+    /// The extent of the region is taken from the shape of `data`;
+    /// any extent in `region` is ignored.
+    pub fn update_region_from_texels<T: TexelType, D: TextureDim>(
+        &self,
+        image_id: ImageId,
+        region: &ImageRegion,
+        data: &ndarray::Array<T, D>,
+        gen_mipmaps: bool,
+    ) -> Result<(), Error> {
+        let d = data.as_slice().ok_or(Error::ImproperDataFormat)?;
+        let region = ImageRegion {
+            extent: Some(ndarray_extent(data)?),
+            ..*region
+        };
+
+        // Guaranteed by `TexelType`: each texel is a packed run of
+        // components.
+        let num_components = base_format_num_components(T::base_format);
+        let values = unsafe {
+            std::slice::from_raw_parts(
+                d.as_ptr() as *const T::Component,
+                d.len() * num_components,
+            )
+        };
+
+        self.update_region(image_id, &region, T::base_format, values, gen_mipmaps)
+    }
+
+    /// Read a region of an image back to the host as an array of
+    /// texels.
+    pub fn read_texels<T: TexelType, D: TextureDim>(
+        &self,
+        image_id: ImageId,
+        region: &ImageRegion,
+    ) -> Result<ndarray::Array<T, D>, Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+        if D::NDIM != Some(image_type_num_dims(image.image_type)) {
+            return Err(Error::BadDataLayout);
+        }
+
+        let (data, extent) =
+            self.read_region_vec::<T::Component>(image_id, T::base_format, region)?;
+        let num_components = base_format_num_components(T::base_format);
+        let texels: Vec<T> = data
+            .chunks_exact(num_components)
+            .map(|c| unsafe { std::ptr::read_unaligned(c.as_ptr() as *const T) })
+            .collect();
+
+        ndarray::Array::from_shape_vec(D::from_extent(extent), texels)
+            .map_err(|_| Error::BadDataLayout)
+    }
+
+    /// Save a single level and layer of an image to disk.
+    ///
+    /// Only color images with normalized or float formats can be
+    /// saved; depth, stencil and integer images are rejected.
+    pub fn save_image<P: AsRef<Path>>(
+        &self,
+        image_id: ImageId,
+        path: P,
+        options: &SaveImageOptions,
+    ) -> Result<(), Error> {
+        let image = self
+            .images
+            .get(image_id)
+            .ok_or(Error::MissingImageId(image_id))?;
+
+        let is_color = !matches!(
+            image.format.base_format(),
+            grr::BaseFormat::Depth | grr::BaseFormat::DepthStencil | grr::BaseFormat::Stencil
+        );
+        if !is_color || format_is_integer(image.format) {
+            return Err(Error::UnsavableFormat(image.format));
+        }
+
+        let region = match image.image_type {
+            grr::ImageType::D3 { .. } => ImageRegion {
+                level: options.level,
+                layer: 0,
+                offset: grr::Offset {
+                    x: 0,
+                    y: 0,
+                    z: options.layer as i32,
+                },
+                extent: Some(grr::Extent {
+                    depth: 1,
+                    ..mip_level_extent(image.image_type, options.level)
+                }),
+            },
+            _ => ImageRegion::whole(options.level, options.layer),
+        };
+
+        let (mut data, extent) =
+            self.read_region_vec::<f32>(image_id, grr::BaseFormat::RGBA, &region)?;
+        if options.flip_vertically {
+            screenshot::flip_rows(
+                &mut data,
+                extent.width as usize * 4,
+                extent.height as usize,
+            );
+        }
+
+        screenshot::save_rgba_f32(
+            path,
+            &data,
+            extent.width,
+            extent.height,
+            options.file_format,
+            options.tone_mapping,
+        )?;
+        Ok(())
+    }
+
+    /// Return the properties of an image.
+    pub fn image_info(&self, image_id: ImageId) -> Option<ImageInfo> {
+        let image = self.images.get(image_id)?;
+        Some(ImageInfo {
+            image_type: image.image_type,
+            format: image.format,
+            extent: image_type_to_full_extent(image.image_type),
+            num_layers: image_type_num_layers(image.image_type),
+            num_mipmap_levels: image.num_mipmap_levels,
+            samples: image_type_samples(image.image_type),
+            num_views: self.image_views(image_id).count(),
+            memory_size: image.memory_size(),
+        })
+    }
+
+    /// Return the ids of every image.
+    pub fn images(&self) -> impl Iterator<Item = ImageId> + '_ {
+        self.images.keys()
+    }
+
+    /// Return the ids of every view created over `image_id`.
+    pub fn image_views(&self, image_id: ImageId) -> impl Iterator<Item = ImageViewId> + '_ {
+        self.views
+            .iter()
+            .filter(move |(_, v)| v.orig_handle == image_id)
+            .map(|(id, _)| id)
+    }
+
+    /// Return the image that a view was created from.
+    pub fn view_image(&self, view: ImageViewId) -> Option<ImageId> {
+        self.views.get(view).map(|v| v.orig_handle)
+    }
+
+    /// Return an estimate of the memory used by all images.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            num_images: self.images.len(),
+            num_views: self.views.len(),
+            total_bytes: self
+                .images
+                .values()
+                .filter_map(Image::memory_size)
+                .fold(0, usize::saturating_add),
+        }
     }
 
     pub fn get_image_handle(&self, image: ImageId) -> Option<grr::Image> {
@@ -0,0 +1,215 @@
+//! Keyboard and mouse state tracking, fed by glutin events.
+//!
+//! Call `InputState::begin_frame` at the start of each frame (on
+//! `Event::NewEvents`) and `InputState::handle_event` for every
+//! event. Input that imgui wants to capture, as reported by
+//! `set_ui_capture`, is hidden from the queries, except for releases.
+use crate::window::GrrImgui;
+use glutin::event::{
+    DeviceEvent, ElementState, Event, ModifiersState, MouseButton, MouseScrollDelta,
+    VirtualKeyCode, WindowEvent,
+};
+use std::collections::HashSet;
+
+/// Lines scrolled per pixel, for touchpads reporting pixel deltas.
+const LINES_PER_PIXEL: f32 = 1.0 / 20.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct InputState {
+    keys_down: HashSet<VirtualKeyCode>,
+    keys_pressed: HashSet<VirtualKeyCode>,
+    keys_released: HashSet<VirtualKeyCode>,
+
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+
+    cursor: Option<(f64, f64)>,
+    cursor_delta: (f64, f64),
+    mouse_motion: (f64, f64),
+    scroll: (f32, f32),
+    modifiers: ModifiersState,
+
+    ui_wants_mouse: bool,
+    ui_wants_keyboard: bool,
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        InputState::default()
+    }
+
+    /// Forget the per-frame state of the previous frame.
+    pub fn begin_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.cursor_delta = (0.0, 0.0);
+        self.mouse_motion = (0.0, 0.0);
+        self.scroll = (0.0, 0.0);
+    }
+
+    /// Set whether the UI is capturing the mouse and keyboard.
+    ///
+    /// While captured, presses are ignored, and the held and pressed
+    /// queries, movement and scrolling of that device report nothing.
+    /// Releases are still reported, so keys held before the UI took
+    /// focus do not stick.
+    pub fn set_ui_capture(&mut self, wants_mouse: bool, wants_keyboard: bool) {
+        self.ui_wants_mouse = wants_mouse;
+        self.ui_wants_keyboard = wants_keyboard;
+    }
+
+    /// Take the capture state from imgui's last frame.
+    pub fn update_ui_capture(&mut self, imgui: &GrrImgui) {
+        let io = imgui.imgui_context.io();
+        self.set_ui_capture(io.want_capture_mouse, io.want_capture_keyboard);
+    }
+
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        match event {
+            Event::WindowEvent { event, .. } => self.handle_window_event(event),
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                self.mouse_motion.0 += delta.0;
+                self.mouse_motion.1 += delta.1;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode {
+                    match input.state {
+                        ElementState::Pressed => {
+                            // Ignore key repeats.
+                            if !self.ui_wants_keyboard && self.keys_down.insert(key) {
+                                self.keys_pressed.insert(key);
+                            }
+                        }
+                        ElementState::Released => {
+                            if self.keys_down.remove(&key) {
+                                self.keys_released.insert(key);
+                            }
+                        }
+                    }
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    if !self.ui_wants_mouse && self.buttons_down.insert(*button) {
+                        self.buttons_pressed.insert(*button);
+                    }
+                }
+                ElementState::Released => {
+                    if self.buttons_down.remove(button) {
+                        self.buttons_released.insert(*button);
+                    }
+                }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some((x, y)) = self.cursor {
+                    self.cursor_delta.0 += position.x - x;
+                    self.cursor_delta.1 += position.y - y;
+                }
+                self.cursor = Some((position.x, position.y));
+            }
+            WindowEvent::CursorLeft { .. } => self.cursor = None,
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (*x, *y),
+                    MouseScrollDelta::PixelDelta(p) => (
+                        p.x as f32 * LINES_PER_PIXEL,
+                        p.y as f32 * LINES_PER_PIXEL,
+                    ),
+                };
+                self.scroll.0 += x;
+                self.scroll.1 += y;
+            }
+            WindowEvent::ModifiersChanged(m) => self.modifiers = *m,
+            WindowEvent::Focused(false) => {
+                // Releases are not delivered while unfocused.
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => {}
+        }
+    }
+
+    /// The key is held down.
+    pub fn key_down(&self, key: VirtualKeyCode) -> bool {
+        !self.ui_wants_keyboard && self.keys_down.contains(&key)
+    }
+
+    /// The key was pressed this frame.
+    pub fn key_pressed(&self, key: VirtualKeyCode) -> bool {
+        !self.ui_wants_keyboard && self.keys_pressed.contains(&key)
+    }
+
+    /// The key was released this frame, whether or not the UI
+    /// captures the keyboard.
+    pub fn key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    /// The mouse button is held down.
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        !self.ui_wants_mouse && self.buttons_down.contains(&button)
+    }
+
+    /// The mouse button was pressed this frame.
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        !self.ui_wants_mouse && self.buttons_pressed.contains(&button)
+    }
+
+    /// The mouse button was released this frame, whether or not the
+    /// UI captures the mouse.
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// Cursor position in physical pixels from the top left of the
+    /// window, if the cursor is over it. Reported even while the UI
+    /// captures the mouse.
+    pub fn cursor_position(&self) -> Option<(f64, f64)> {
+        self.cursor
+    }
+
+    /// Cursor movement this frame, in physical pixels.
+    pub fn cursor_delta(&self) -> (f64, f64) {
+        if self.ui_wants_mouse {
+            (0.0, 0.0)
+        } else {
+            self.cursor_delta
+        }
+    }
+
+    /// Raw mouse movement this frame, unaffected by cursor
+    /// acceleration or the window edges.
+    pub fn mouse_motion(&self) -> (f64, f64) {
+        if self.ui_wants_mouse {
+            (0.0, 0.0)
+        } else {
+            self.mouse_motion
+        }
+    }
+
+    /// Scrolling this frame, in lines.
+    pub fn scroll(&self) -> (f32, f32) {
+        if self.ui_wants_mouse {
+            (0.0, 0.0)
+        } else {
+            self.scroll
+        }
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+}
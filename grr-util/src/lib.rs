@@ -1,15 +1,31 @@
+pub mod app;
+pub mod async_screenshot;
+pub mod atlas;
 pub mod color;
+pub mod compare;
+pub mod debug;
 pub mod image_format;
 pub mod image_manager;
+pub mod input;
 pub mod mesh;
+pub mod mipmap;
+pub mod procedural;
+pub mod raw_gl;
+pub mod recorder;
+pub mod render_target;
 pub mod screenshot;
 pub mod shader_manager;
+pub mod texture_container;
+pub mod tiled_screenshot;
+pub mod timing;
 pub mod vertex;
 pub mod window;
 
+pub use app::App;
 pub use color::{hex_constant_rgb, hex_constant_rgba};
 pub use image_manager::ImageManager;
 pub use num_traits::Zero;
+pub use render_target::{RenderTarget, RenderTargetDesc};
 pub use shader_manager::{ManagedPipeline, ShaderDesc, ShaderManager};
 pub use vertex::GrrVertex;
-pub use window::{GrrBuilder, GrrHeadless, GrrImgui, GrrWindow};
+pub use window::{GrrBuilder, GrrHeadless, GrrImgui, GrrWindow, VideoModeRequest, WindowMode};
@@ -0,0 +1,326 @@
+//! CPU mip chain generation.
+//!
+//! Unlike `grr::Device::generate_mipmaps`, which box filters the
+//! stored values, this allows choosing the filter, downsamples sRGB
+//! data in linear space, and handles normal maps and alpha-tested
+//! textures.
+use crate::image_manager::{self, ImageId, ImageManager};
+use nalgebra::{Vector3, Vector4};
+use ndarray::Array2;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MipError {
+    #[error("invalid mipmap filter {0:?}")]
+    InvalidFilter(MipFilter),
+
+    #[error("image manager error")]
+    Image(#[from] image_manager::Error),
+}
+
+/// Reconstruction filter used when downsampling.
+///
+/// Filters built directly from the variants are checked by
+/// `validate` when used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MipFilter {
+    /// Average of the texels covered by each output texel.
+    Box,
+    /// Kaiser-windowed sinc with the given `alpha` and half-width, in
+    /// output texels.
+    Kaiser { alpha: f32, width: f32 },
+    /// Lanczos-windowed sinc with `a` lobes.
+    Lanczos(u32),
+}
+
+impl MipFilter {
+    /// Kaiser-windowed sinc filter. Fails unless `width` is above 0.5,
+    /// so that every output texel has a tap, and `alpha` is
+    /// non-negative and small enough for the window to be finite.
+    pub fn kaiser(alpha: f32, width: f32) -> Result<MipFilter, MipError> {
+        MipFilter::Kaiser { alpha, width }.validate()
+    }
+
+    /// Lanczos-windowed sinc filter. Fails if `a` is zero.
+    pub fn lanczos(a: u32) -> Result<MipFilter, MipError> {
+        MipFilter::Lanczos(a).validate()
+    }
+
+    /// Return the filter if its parameters give every output texel a
+    /// positive weight.
+    pub fn validate(self) -> Result<MipFilter, MipError> {
+        let valid = match self {
+            MipFilter::Box => true,
+            // The nearest source texel is at most half an output
+            // texel from the center.
+            MipFilter::Kaiser { alpha, width } => {
+                width > 0.5 && width.is_finite() && alpha >= 0.0 && bessel_i0(alpha).is_finite()
+            }
+            MipFilter::Lanczos(a) => a > 0,
+        };
+        if valid {
+            Ok(self)
+        } else {
+            Err(MipError::InvalidFilter(self))
+        }
+    }
+
+    /// Half-width of the filter, in output texels.
+    fn support(self) -> f32 {
+        match self {
+            MipFilter::Box => 0.5,
+            MipFilter::Kaiser { width, .. } => width,
+            MipFilter::Lanczos(a) => a as f32,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            MipFilter::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            MipFilter::Kaiser { alpha, width } => {
+                if x >= width {
+                    0.0
+                } else {
+                    let r = x / width;
+                    sinc(x) * bessel_i0(alpha * (1.0 - r * r).sqrt()) / bessel_i0(alpha)
+                }
+            }
+            MipFilter::Lanczos(a) => {
+                let a = a as f32;
+                if x >= a {
+                    0.0
+                } else {
+                    sinc(x) * sinc(x / a)
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-6 {
+        1.0
+    } else {
+        let px = std::f32::consts::PI * x;
+        px.sin() / px
+    }
+}
+
+/// Zeroth-order modified Bessel function of the first kind.
+fn bessel_i0(x: f32) -> f32 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let y = x * x / 4.0;
+    for k in 1..32 {
+        term *= y / (k * k) as f32;
+        sum += term;
+        if term < sum * 1e-7 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Options for `generate_mip_chain`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MipOptions {
+    pub filter: MipFilter,
+
+    /// Color channels are sRGB-encoded, and are filtered in linear space.
+    pub srgb: bool,
+
+    /// Color channels hold a normal encoded as `n * 0.5 + 0.5`, which
+    /// is renormalized on every level.
+    pub normal_map: bool,
+
+    /// Scale the alpha of each level so that the fraction of texels
+    /// above this cutoff matches level 0.
+    pub alpha_cutoff: Option<f32>,
+}
+
+impl Default for MipOptions {
+    fn default() -> MipOptions {
+        MipOptions {
+            filter: MipFilter::Box,
+            srgb: false,
+            normal_map: false,
+            alpha_cutoff: None,
+        }
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Map each color channel, leaving alpha untouched.
+fn map_rgb(v: Vector4<f32>, f: impl Fn(f32) -> f32) -> Vector4<f32> {
+    Vector4::new(f(v.x), f(v.y), f(v.z), v.w)
+}
+
+/// Resample along one axis to `dst_len` texels.
+fn resample_axis(
+    src: &Array2<Vector4<f32>>,
+    dst_len: usize,
+    axis: usize,
+    filter: MipFilter,
+) -> Array2<Vector4<f32>> {
+    let src_len = src.shape()[axis];
+    let scale = src_len as f32 / dst_len as f32;
+    let radius = filter.support() * scale;
+
+    // Precompute the taps of each output texel, clamping to the edge.
+    let taps: Vec<Vec<(usize, f32)>> = (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let first = (center - radius).floor() as isize;
+            let last = (center + radius).ceil() as isize;
+            let mut taps: Vec<(usize, f32)> = (first..=last)
+                .map(|j| {
+                    let w = filter.weight((j as f32 + 0.5 - center) / scale);
+                    (j.max(0).min(src_len as isize - 1) as usize, w)
+                })
+                .filter(|&(_, w)| w != 0.0)
+                .collect();
+            let total: f32 = taps.iter().map(|t| t.1).sum();
+            taps.iter_mut().for_each(|t| t.1 /= total);
+            taps
+        })
+        .collect();
+
+    let mut shape = [src.nrows(), src.ncols()];
+    shape[axis] = dst_len;
+    Array2::from_shape_fn((shape[0], shape[1]), |(y, x)| {
+        taps[if axis == 0 { y } else { x }]
+            .iter()
+            .fold(Vector4::zeros(), |acc, &(j, w)| {
+                let s = if axis == 0 { src[(j, x)] } else { src[(y, j)] };
+                acc + s * w
+            })
+    })
+}
+
+/// Fraction of texels whose alpha is above `cutoff`.
+fn alpha_coverage(level: &Array2<Vector4<f32>>, cutoff: f32, scale: f32) -> f32 {
+    let covered = level.iter().filter(|v| v.w * scale > cutoff).count();
+    covered as f32 / level.len().max(1) as f32
+}
+
+/// Scale the alpha of `level` so that its coverage approaches `target`.
+fn preserve_coverage(level: &mut Array2<Vector4<f32>>, cutoff: f32, target: f32) {
+    let (mut lo, mut hi) = (0.0f32, 4.0f32);
+    for _ in 0..16 {
+        let mid = 0.5 * (lo + hi);
+        if alpha_coverage(level, cutoff, mid) < target {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    let scale = 0.5 * (lo + hi);
+    level.iter_mut().for_each(|v| v.w = (v.w * scale).min(1.0));
+}
+
+/// Generate the full mip chain of a 2D RGBA image with values in
+/// [0, 1], including level 0 itself.
+///
+/// Fails if the filter is invalid, see `MipFilter::validate`.
+pub fn generate_mip_chain(
+    level0: &Array2<Vector4<f32>>,
+    options: &MipOptions,
+) -> Result<Vec<Array2<Vector4<f32>>>, MipError> {
+    options.filter.validate()?;
+
+    // Work in the space the filter should operate in.
+    let decode = |v: Vector4<f32>| {
+        if options.normal_map {
+            map_rgb(v, |c| c * 2.0 - 1.0)
+        } else if options.srgb {
+            map_rgb(v, srgb_to_linear)
+        } else {
+            v
+        }
+    };
+    let encode = |v: Vector4<f32>| {
+        let v = if options.normal_map {
+            let n = v.xyz().try_normalize(1e-8).unwrap_or_else(Vector3::z);
+            Vector4::new(n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5, v.w)
+        } else if options.srgb {
+            map_rgb(v, |c| linear_to_srgb(c.max(0.0)))
+        } else {
+            v
+        };
+        v.map(|c| c.max(0.0).min(1.0))
+    };
+
+    let coverage = options
+        .alpha_cutoff
+        .map(|cutoff| (cutoff, alpha_coverage(level0, cutoff, 1.0)));
+
+    let mut levels = vec![level0.clone()];
+    let mut current = level0.mapv(decode);
+    while current.nrows() > 1 || current.ncols() > 1 {
+        let h = (current.nrows() / 2).max(1);
+        let w = (current.ncols() / 2).max(1);
+        let rows = resample_axis(&current, h, 0, options.filter);
+        current = resample_axis(&rows, w, 1, options.filter);
+
+        let mut level = current.mapv(encode);
+        if let Some((cutoff, target)) = coverage {
+            preserve_coverage(&mut level, cutoff, target);
+        }
+        if options.normal_map {
+            current = level.mapv(decode);
+        }
+        levels.push(level);
+    }
+
+    Ok(levels)
+}
+
+/// Generate the full mip chain of an 8-bit RGBA image.
+pub fn generate_mip_chain_rgba8(
+    level0: &Array2<Vector4<u8>>,
+    options: &MipOptions,
+) -> Result<Vec<Array2<Vector4<u8>>>, MipError> {
+    let level0 = level0.mapv(|v| v.map(|c| c as f32 / 255.0));
+    Ok(generate_mip_chain(&level0, options)?
+        .into_iter()
+        .map(|l| l.mapv(|v| v.map(|c| (c * 255.0).round() as u8)))
+        .collect())
+}
+
+/// Generate a mip chain on the CPU and upload every level into a new
+/// 8-bit RGBA image, stored as sRGB if `options.srgb` is set.
+pub fn create_image_with_mips(
+    images: &mut ImageManager,
+    level0: &Array2<Vector4<u8>>,
+    options: &MipOptions,
+) -> Result<ImageId, MipError> {
+    let levels = generate_mip_chain_rgba8(level0, options)?;
+    let format = if options.srgb {
+        grr::Format::R8G8B8A8_SRGB
+    } else {
+        grr::Format::R8G8B8A8_UNORM
+    };
+    Ok(images.create_image_from_mip_chain(&levels, format)?)
+}
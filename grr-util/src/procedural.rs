@@ -0,0 +1,476 @@
+//! Procedural test textures.
+//!
+//! Every generator returns an array in the layout expected by
+//! `ImageManager::create_image_from_ndarray`, with row 0 first.
+//! Random generators are deterministic for a given seed.
+use crate::image_format::{TexelBaseType, TextureComponentDim, TextureDim};
+use crate::image_manager::{self, ImageId, ImageManager};
+use nalgebra::{Vector1, Vector4};
+use ndarray::{Array2, Array3};
+
+/// Small, seedable pseudo-random generator (SplitMix64).
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in [0, 1).
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform index in [0, n).
+    fn next_index(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// Checkerboard of `cell_size`-texel squares, starting with `a` at
+/// the origin.
+pub fn checkerboard(
+    width: usize,
+    height: usize,
+    cell_size: usize,
+    a: Vector4<u8>,
+    b: Vector4<u8>,
+) -> Array2<Vector4<u8>> {
+    let cell_size = cell_size.max(1);
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        if (x / cell_size + y / cell_size) % 2 == 0 {
+            a
+        } else {
+            b
+        }
+    })
+}
+
+/// UV debugging grid: red and green encode u and v, with white grid
+/// lines dividing the texture into `divisions` cells per axis.
+pub fn uv_grid(width: usize, height: usize, divisions: usize) -> Array2<Vector4<u8>> {
+    let divisions = divisions.max(1);
+    let on_line = |i: usize, n: usize| (i * divisions) % n < divisions;
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        if on_line(x, width) || on_line(y, height) {
+            Vector4::new(255, 255, 255, 255)
+        } else {
+            let u = (x as f32 + 0.5) / width as f32;
+            let v = (y as f32 + 0.5) / height as f32;
+            Vector4::new((u * 255.0) as u8, (v * 255.0) as u8, 0, 255)
+        }
+    })
+}
+
+/// Direction along which a gradient varies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GradientDirection {
+    Horizontal,
+    Vertical,
+    Radial,
+}
+
+/// Linear interpolation from `start` to `end`. Radial gradients
+/// start at the center and reach `end` at the nearest edge.
+pub fn gradient(
+    width: usize,
+    height: usize,
+    start: Vector4<f32>,
+    end: Vector4<f32>,
+    direction: GradientDirection,
+) -> Array2<Vector4<f32>> {
+    let step = |i: usize, n: usize| {
+        if n > 1 {
+            i as f32 / (n - 1) as f32
+        } else {
+            0.0
+        }
+    };
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        let t = match direction {
+            GradientDirection::Horizontal => step(x, width),
+            GradientDirection::Vertical => step(y, height),
+            GradientDirection::Radial => {
+                let dx = step(x, width) * 2.0 - 1.0;
+                let dy = step(y, height) * 2.0 - 1.0;
+                (dx * dx + dy * dy).sqrt().min(1.0)
+            }
+        };
+        start.lerp(&end, t)
+    })
+}
+
+/// Independent uniform values in [0, 1).
+pub fn white_noise(width: usize, height: usize, seed: u64) -> Array2<Vector1<f32>> {
+    let mut rng = SplitMix64(seed);
+    Array2::from_shape_simple_fn((height, width), || Vector1::new(rng.next_f32()))
+}
+
+/// Tileable blue noise threshold map, generated with the
+/// void-and-cluster method.
+///
+/// Every value in the result is distinct, spread evenly over [0, 1).
+/// Generation is quadratic in the number of texels, so this is meant
+/// for small tiles (e.g. 64x64).
+pub fn blue_noise(size: usize, seed: u64) -> Array2<Vector1<f32>> {
+    const SIGMA: f32 = 1.5;
+    let n = size * size;
+    if n == 0 {
+        return Array2::from_elem((size, size), Vector1::new(0.0));
+    }
+
+    // Gaussian weight for every toroidal offset.
+    let kernel: Vec<f32> = (0..n)
+        .map(|i| {
+            let wrap = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (wrap(i % size), wrap(i / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        })
+        .collect();
+
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.0f32; n];
+    let splat = |energy: &mut [f32], p: usize, sign: f32| {
+        let (px, py) = (p % size, p / size);
+        for (i, e) in energy.iter_mut().enumerate() {
+            let dx = (i % size + size - px) % size;
+            let dy = (i / size + size - py) % size;
+            *e += sign * kernel[dy * size + dx];
+        }
+    };
+    let tightest = |pattern: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| pattern[i])
+            .max_by(|&a, &b| energy[a].partial_cmp(&energy[b]).unwrap())
+    };
+    let largest_void = |pattern: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| !pattern[i])
+            .min_by(|&a, &b| energy[a].partial_cmp(&energy[b]).unwrap())
+    };
+
+    // Initial random pattern covering a tenth of the texels.
+    let mut rng = SplitMix64(seed);
+    let num_initial = (n / 10).max(1);
+    let mut count = 0;
+    while count < num_initial {
+        let p = rng.next_index(n);
+        if !pattern[p] {
+            pattern[p] = true;
+            splat(&mut energy, p, 1.0);
+            count += 1;
+        }
+    }
+
+    // Move points from clusters into voids until the pattern is stable.
+    for _ in 0..n {
+        let cluster = tightest(&pattern, &energy).unwrap();
+        pattern[cluster] = false;
+        splat(&mut energy, cluster, -1.0);
+        let void = largest_void(&pattern, &energy).unwrap();
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0usize; n];
+
+    // Rank the initial points by removing the tightest clusters first.
+    let mut initial = pattern.clone();
+    let mut initial_energy = energy.clone();
+    for r in (0..num_initial).rev() {
+        let cluster = tightest(&initial, &initial_energy).unwrap();
+        initial[cluster] = false;
+        splat(&mut initial_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    // Rank the remaining points by filling the largest voids.
+    for r in num_initial..n {
+        let void = largest_void(&pattern, &energy).unwrap();
+        pattern[void] = true;
+        splat(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+
+    Array2::from_shape_fn((size, size), |(y, x)| {
+        Vector1::new(rank[y * size + x] as f32 / n as f32)
+    })
+}
+
+/// Lattice permutation table for gradient noise.
+struct Permutation([u8; 256]);
+
+impl Permutation {
+    fn new(seed: u64) -> Permutation {
+        let mut rng = SplitMix64(seed);
+        let mut p = [0u8; 256];
+        for (i, v) in p.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        for i in (1..256).rev() {
+            p.swap(i, rng.next_index(i + 1));
+        }
+        Permutation(p)
+    }
+
+    fn hash(&self, coords: &[i32]) -> usize {
+        coords
+            .iter()
+            .fold(0usize, |h, &c| self.0[(h + (c & 255) as usize) & 255] as usize)
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Single octave of Perlin noise in [-1, 1], repeating every
+/// `period` lattice cells along each axis.
+fn perlin_octave(perm: &Permutation, p: &[f32; 3], period: i32, dims: usize) -> f32 {
+    const GRAD3: [[f32; 3]; 12] = [
+        [1.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0],
+        [1.0, -1.0, 0.0],
+        [-1.0, -1.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [-1.0, 0.0, 1.0],
+        [1.0, 0.0, -1.0],
+        [-1.0, 0.0, -1.0],
+        [0.0, 1.0, 1.0],
+        [0.0, -1.0, 1.0],
+        [0.0, 1.0, -1.0],
+        [0.0, -1.0, -1.0],
+    ];
+    const GRAD2: [[f32; 3]; 8] = [
+        [1.0, 0.0, 0.0],
+        [-1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, -1.0, 0.0],
+        [0.7071, 0.7071, 0.0],
+        [-0.7071, 0.7071, 0.0],
+        [0.7071, -0.7071, 0.0],
+        [-0.7071, -0.7071, 0.0],
+    ];
+
+    let cell = [p[0].floor(), p[1].floor(), p[2].floor()];
+    let frac = [p[0] - cell[0], p[1] - cell[1], p[2] - cell[2]];
+    let corner_grad = |corner: usize| {
+        let mut lattice = [0i32; 3];
+        let mut dot = 0.0;
+        let h = {
+            for (axis, l) in lattice.iter_mut().enumerate().take(dims) {
+                let offset = ((corner >> axis) & 1) as i32;
+                *l = (cell[axis] as i32 + offset).rem_euclid(period);
+            }
+            perm.hash(&lattice[..dims])
+        };
+        let g = if dims == 3 {
+            GRAD3[h % 12]
+        } else {
+            GRAD2[h % 8]
+        };
+        for axis in 0..dims {
+            let offset = ((corner >> axis) & 1) as f32;
+            dot += g[axis] * (frac[axis] - offset);
+        }
+        dot
+    };
+
+    let u = [fade(frac[0]), fade(frac[1]), fade(frac[2])];
+    let num_corners = 1 << dims;
+    let mut values: Vec<f32> = (0..num_corners).map(corner_grad).collect();
+
+    // Interpolate along each axis in turn, halving the corner count.
+    for axis in 0..dims {
+        let half = values.len() / 2;
+        values = (0..half)
+            .map(|i| lerp(values[2 * i], values[2 * i + 1], u[axis]))
+            .collect();
+    }
+    values[0]
+}
+
+/// Largest number of octaves summed by the noise generators; more
+/// are ignored. Further octaves would be far below the precision of
+/// the result, and overflow the lattice period.
+pub const MAX_OCTAVES: u32 = 16;
+
+/// Sum `octaves` of Perlin noise, mapped to [0, 1].
+fn fbm(perm: &Permutation, p: [f32; 3], frequency: u32, octaves: u32, dims: usize) -> f32 {
+    let mut total = 0.0;
+    let mut amplitude = 1.0;
+    let mut norm = 0.0;
+    for octave in 0..octaves.max(1).min(MAX_OCTAVES) {
+        let scale = (1u32 << octave) as f32 * frequency as f32;
+        let period = frequency
+            .saturating_mul(1 << octave)
+            .max(1)
+            .min(i32::MAX as u32) as i32;
+        let q = [p[0] * scale, p[1] * scale, p[2] * scale];
+        total += amplitude * perlin_octave(perm, &q, period, dims);
+        norm += amplitude;
+        amplitude *= 0.5;
+    }
+    (total / norm * 0.5 + 0.5).max(0.0).min(1.0)
+}
+
+/// Tileable 2D Perlin noise with `frequency` lattice cells across the
+/// texture in the first octave, in [0, 1]. At most `MAX_OCTAVES`
+/// octaves are summed.
+pub fn perlin_2d(
+    width: usize,
+    height: usize,
+    frequency: u32,
+    octaves: u32,
+    seed: u64,
+) -> Array2<Vector1<f32>> {
+    let perm = Permutation::new(seed);
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        let p = [x as f32 / width as f32, y as f32 / height as f32, 0.0];
+        Vector1::new(fbm(&perm, p, frequency, octaves, 2))
+    })
+}
+
+/// Tileable 3D Perlin noise volume, in [0, 1].
+pub fn perlin_3d(
+    width: usize,
+    height: usize,
+    depth: usize,
+    frequency: u32,
+    octaves: u32,
+    seed: u64,
+) -> Array3<Vector1<f32>> {
+    let perm = Permutation::new(seed);
+    Array3::from_shape_fn((depth, height, width), |(z, y, x)| {
+        let p = [
+            x as f32 / width as f32,
+            y as f32 / height as f32,
+            z as f32 / depth as f32,
+        ];
+        Vector1::new(fbm(&perm, p, frequency, octaves, 3))
+    })
+}
+
+/// Single octave of 2D simplex noise in roughly [-1, 1].
+fn simplex_octave(perm: &Permutation, x: f32, y: f32) -> f32 {
+    const GRAD: [[f32; 2]; 8] = [
+        [1.0, 0.0],
+        [-1.0, 0.0],
+        [0.0, 1.0],
+        [0.0, -1.0],
+        [0.7071, 0.7071],
+        [-0.7071, 0.7071],
+        [0.7071, -0.7071],
+        [-0.7071, -0.7071],
+    ];
+    let f2 = 0.5 * (3.0f32.sqrt() - 1.0);
+    let g2 = (3.0 - 3.0f32.sqrt()) / 6.0;
+
+    let s = (x + y) * f2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * g2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1.0, 0.0) } else { (0.0, 1.0) };
+
+    let corners = [
+        (x0, y0, 0.0, 0.0),
+        (x0 - i1 + g2, y0 - j1 + g2, i1, j1),
+        (x0 - 1.0 + 2.0 * g2, y0 - 1.0 + 2.0 * g2, 1.0, 1.0),
+    ];
+
+    let total: f32 = corners
+        .iter()
+        .map(|&(dx, dy, oi, oj)| {
+            let t = 0.5 - dx * dx - dy * dy;
+            if t < 0.0 {
+                0.0
+            } else {
+                let h = perm.hash(&[(i + oi) as i32, (j + oj) as i32]);
+                let g = GRAD[h % 8];
+                t.powi(4) * (g[0] * dx + g[1] * dy)
+            }
+        })
+        .sum();
+    70.0 * total
+}
+
+/// 2D simplex noise with `frequency` features across the texture in
+/// the first octave, in [0, 1]. Unlike `perlin_2d`, the result does
+/// not tile.
+pub fn simplex_2d(
+    width: usize,
+    height: usize,
+    frequency: f32,
+    octaves: u32,
+    seed: u64,
+) -> Array2<Vector1<f32>> {
+    let perm = Permutation::new(seed);
+    Array2::from_shape_fn((height, width), |(y, x)| {
+        let (u, v) = (x as f32 / width as f32, y as f32 / height as f32);
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut norm = 0.0;
+        for octave in 0..octaves.max(1).min(MAX_OCTAVES) {
+            let scale = frequency * (1u32 << octave) as f32;
+            total += amplitude * simplex_octave(&perm, u * scale, v * scale);
+            norm += amplitude;
+            amplitude *= 0.5;
+        }
+        Vector1::new((total / norm * 0.5 + 0.5).max(0.0).min(1.0))
+    })
+}
+
+/// Upload a generated array as a new image with a full mip chain.
+pub fn upload<PC: TexelBaseType, D: TextureDim, CD: TextureComponentDim>(
+    images: &mut ImageManager,
+    data: &ndarray::Array<nalgebra::VectorN<PC, CD>, D>,
+) -> Result<ImageId, image_manager::Error>
+where
+    nalgebra::DefaultAllocator: nalgebra::base::allocator::Allocator<PC, CD>,
+{
+    let largest = data.shape().iter().cloned().max().unwrap_or(1).max(1) as u32;
+    let num_levels = 32 - largest.leading_zeros();
+    images.create_image_from_ndarray(data, num_levels, true)
+}
+
+/// Create a checkerboard image.
+pub fn checkerboard_image(
+    images: &mut ImageManager,
+    size: usize,
+    cell_size: usize,
+    a: Vector4<u8>,
+    b: Vector4<u8>,
+) -> Result<ImageId, image_manager::Error> {
+    upload(images, &checkerboard(size, size, cell_size, a, b))
+}
+
+/// Create a UV debugging grid image.
+pub fn uv_grid_image(
+    images: &mut ImageManager,
+    size: usize,
+    divisions: usize,
+) -> Result<ImageId, image_manager::Error> {
+    upload(images, &uv_grid(size, size, divisions))
+}
+
+/// Create a tileable 3D Perlin noise volume image.
+pub fn perlin_3d_image(
+    images: &mut ImageManager,
+    size: usize,
+    frequency: u32,
+    octaves: u32,
+    seed: u64,
+) -> Result<ImageId, image_manager::Error> {
+    upload(images, &perlin_3d(size, size, size, frequency, octaves, seed))
+}
@@ -0,0 +1,39 @@
+//! Raw OpenGL calls, for functionality that `grr` does not expose.
+//!
+//! The `gl` crate keeps its own function pointers, separate from
+//! those of `grr::Device`. `GrrBuilder` loads them along with the
+//! device; when a device is created some other way, call `load_with`
+//! with the same loader. Functions making raw calls check that their
+//! entry points are loaded first, and fail with `NotLoaded` if not.
+use thiserror::Error;
+
+/// A raw GL function was called before being loaded.
+#[derive(Debug, Clone, Copy, Error)]
+#[error("OpenGL function gl{0} is not loaded (see `raw_gl::load_with`)")]
+pub struct NotLoaded(pub &'static str);
+
+/// Load the raw GL function pointers, with the loader passed to
+/// `grr::Device::new`.
+pub fn load_with<F>(loader: F)
+where
+    F: FnMut(&'static str) -> *const std::os::raw::c_void,
+{
+    gl::load_with(loader);
+}
+
+/// Return the first function in `functions` that is not loaded, as
+/// pairs of name and loaded state.
+pub(crate) fn require(functions: &[(&'static str, bool)]) -> Result<(), NotLoaded> {
+    match functions.iter().find(|(_, loaded)| !loaded) {
+        Some((name, _)) => Err(NotLoaded(name)),
+        None => Ok(()),
+    }
+}
+
+/// Check that each of the named `gl` functions is loaded.
+macro_rules! require_gl {
+    ($($f:ident),+ $(,)?) => {
+        $crate::raw_gl::require(&[$((stringify!($f), gl::$f::is_loaded())),+])
+    };
+}
+pub(crate) use require_gl;
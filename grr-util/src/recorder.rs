@@ -0,0 +1,286 @@
+//! Recording of rendered frames to image sequences or video.
+//!
+//! Frames are captured with `AsyncScreenshots`, so encoding happens
+//! off the render thread.
+use crate::async_screenshot::AsyncScreenshots;
+use crate::render_target::RenderTarget;
+use crate::screenshot::{Screenshot, ScreenshotError};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Where recorded frames are written.
+#[derive(Debug, Clone)]
+pub enum RecordingSink {
+    /// Numbered PNG files in `directory`.
+    ///
+    /// The run of `#` characters in `pattern` is replaced by the
+    /// zero-padded frame number, e.g. `frame_#####.png`.
+    PngSequence { directory: PathBuf, pattern: String },
+
+    /// Raw frames piped to an `ffmpeg` process, encoding a video at
+    /// `path` with `fps` frames per second.
+    Ffmpeg { path: PathBuf, fps: u32 },
+}
+
+/// Options for `FrameRecorder`.
+#[derive(Debug, Clone)]
+pub struct RecorderOptions {
+    pub sink: RecordingSink,
+
+    /// Record one frame out of every `every_nth`.
+    pub every_nth: u32,
+}
+
+impl RecorderOptions {
+    /// Record every frame to `directory/frame_#####.png`.
+    pub fn png_sequence<P: Into<PathBuf>>(directory: P) -> RecorderOptions {
+        RecorderOptions {
+            sink: RecordingSink::PngSequence {
+                directory: directory.into(),
+                pattern: String::from("frame_#####.png"),
+            },
+            every_nth: 1,
+        }
+    }
+
+    /// Record every frame to a video at `path`.
+    pub fn ffmpeg<P: Into<PathBuf>>(path: P, fps: u32) -> RecorderOptions {
+        RecorderOptions {
+            sink: RecordingSink::Ffmpeg {
+                path: path.into(),
+                fps,
+            },
+            every_nth: 1,
+        }
+    }
+}
+
+/// Expand the run of `#` characters in `pattern` to `index`, padded
+/// with zeros to the length of the run. Without a run, the index is
+/// appended before the extension.
+pub fn frame_file_name(pattern: &str, index: u64) -> String {
+    match pattern.find('#') {
+        Some(start) => {
+            let len = pattern[start..].chars().take_while(|&c| c == '#').count();
+            format!(
+                "{}{:0width$}{}",
+                &pattern[..start],
+                index,
+                &pattern[start + len..],
+                width = len
+            )
+        }
+        None => match pattern.rfind('.') {
+            Some(dot) => format!("{}{}{}", &pattern[..dot], index, &pattern[dot..]),
+            None => format!("{}{}", pattern, index),
+        },
+    }
+}
+
+/// Whether an `ffmpeg` binary can be run.
+pub fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+}
+
+/// A running `ffmpeg` process, fed raw RGBA frames of a fixed size.
+struct FfmpegPipe {
+    child: Child,
+    stdin: Arc<Mutex<ChildStdin>>,
+    width: u32,
+    height: u32,
+}
+
+impl FfmpegPipe {
+    fn spawn(path: &PathBuf, fps: u32, width: u32, height: u32) -> std::io::Result<FfmpegPipe> {
+        let mut child = Command::new("ffmpeg")
+            .args(&["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s")
+            .arg(format!("{}x{}", width, height))
+            .arg("-r")
+            .arg(fps.to_string())
+            .args(&["-i", "-", "-pix_fmt", "yuv420p"])
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+
+        Ok(FfmpegPipe {
+            child,
+            stdin: Arc::new(Mutex::new(stdin)),
+            width,
+            height,
+        })
+    }
+
+    /// Close the stream and wait for `ffmpeg` to finish encoding.
+    ///
+    /// Every frame handler holding the pipe must have run, so that
+    /// this is the last reference to stdin.
+    fn close(self) -> std::io::Result<std::process::ExitStatus> {
+        let FfmpegPipe { mut child, stdin, .. } = self;
+        drop(stdin);
+        child.wait()
+    }
+}
+
+/// Captures every Nth frame from a framebuffer.
+pub struct FrameRecorder<'d> {
+    capture: AsyncScreenshots<'d>,
+    options: RecorderOptions,
+    ffmpeg: Option<FfmpegPipe>,
+
+    /// Frames seen, and frames recorded.
+    num_frames: u64,
+    num_recorded: u64,
+
+    /// First error reported by a capture since the last call to
+    /// `record`.
+    error: Option<ScreenshotError>,
+}
+
+impl<'d> FrameRecorder<'d> {
+    /// Start a recording.
+    ///
+    /// For PNG sequences the output directory is created; for video
+    /// the `ffmpeg` binary must be available.
+    pub fn new(device: &'d grr::Device, options: RecorderOptions) -> Result<Self, ScreenshotError> {
+        match &options.sink {
+            RecordingSink::PngSequence { directory, .. } => std::fs::create_dir_all(directory)?,
+            RecordingSink::Ffmpeg { .. } => {
+                if !ffmpeg_available() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "ffmpeg binary not found",
+                    )
+                    .into());
+                }
+            }
+        }
+
+        Ok(FrameRecorder {
+            capture: AsyncScreenshots::new(device),
+            options,
+            ffmpeg: None,
+            num_frames: 0,
+            num_recorded: 0,
+            error: None,
+        })
+    }
+
+    /// Number of frames recorded so far.
+    pub fn num_recorded(&self) -> u64 {
+        self.num_recorded
+    }
+
+    /// Call once per frame, after rendering into `src_framebuffer`.
+    /// Captures the frame if it is one of every Nth.
+    ///
+    /// Errors from encoding earlier frames are reported here.
+    pub fn record(
+        &mut self,
+        src_framebuffer: grr::Framebuffer,
+        region: grr::Region,
+    ) -> Result<(), ScreenshotError> {
+        let frame = self.num_frames;
+        self.num_frames += 1;
+
+        if frame % self.options.every_nth.max(1) as u64 == 0 {
+            let index = self.num_recorded;
+            let handler = self.frame_handler(index, region)?;
+            self.capture.capture_with(src_framebuffer, region, 0, handler)?;
+            self.num_recorded += 1;
+        }
+
+        for r in self.capture.poll() {
+            if let (Err(e), None) = (r.result, &self.error) {
+                self.error = Some(e);
+            }
+        }
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Record the color of a render target.
+    pub fn record_target(&mut self, target: &RenderTarget) -> Result<(), ScreenshotError> {
+        self.record(target.framebuffer(), target.region())
+    }
+
+    /// Wait for every captured frame to be written, and close the
+    /// video, if any.
+    ///
+    /// Dropping the recorder does the same, but discards errors.
+    pub fn finish(mut self) -> Result<(), ScreenshotError> {
+        let mut error = self.error.take();
+        for r in self.capture.wait_all() {
+            if let (Err(e), None) = (r.result, &error) {
+                error = Some(e);
+            }
+        }
+
+        if let Some(pipe) = self.ffmpeg.take() {
+            let status = pipe.close()?;
+            if !status.success() && error.is_none() {
+                error = Some(
+                    std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("ffmpeg exited with {}", status),
+                    )
+                    .into(),
+                );
+            }
+        }
+
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Build the handler writing frame `index` to the sink.
+    fn frame_handler(
+        &mut self,
+        index: u64,
+        region: grr::Region,
+    ) -> Result<Box<dyn FnOnce(Screenshot) -> Result<(), ScreenshotError> + Send>, ScreenshotError>
+    {
+        match &self.options.sink {
+            RecordingSink::PngSequence { directory, pattern } => {
+                let path = directory.join(frame_file_name(pattern, index));
+                Ok(Box::new(move |shot: Screenshot| shot.save(path)))
+            }
+            RecordingSink::Ffmpeg { path, fps } => {
+                let (width, height) = (region.w as u32, region.h as u32);
+                if self.ffmpeg.is_none() {
+                    self.ffmpeg = Some(FfmpegPipe::spawn(path, *fps, width, height)?);
+                }
+                let pipe = self.ffmpeg.as_ref().unwrap();
+                if (pipe.width, pipe.height) != (width, height) {
+                    return Err(ScreenshotError::InvalidRegion(region));
+                }
+
+                let stdin = pipe.stdin.clone();
+                Ok(Box::new(move |shot: Screenshot| {
+                    let mut stdin = stdin.lock().expect("ffmpeg pipe poisoned");
+                    stdin.write_all(&shot.pixels)?;
+                    Ok(())
+                }))
+            }
+        }
+    }
+}
+
+impl<'d> Drop for FrameRecorder<'d> {
+    /// Write the frames already captured and close the video, as
+    /// `finish` does, ignoring errors.
+    fn drop(&mut self) {
+        self.capture.wait_all();
+        if let Some(pipe) = self.ffmpeg.take() {
+            let _ = pipe.close();
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Offscreen render targets built from managed images.
+use crate::image_manager::{Error, ImageId, ImageManager, ImageViewId, PendingStorage};
+
+/// Description of the attachments of a `RenderTarget`.
+#[derive(Debug, Clone)]
+pub struct RenderTargetDesc {
+    width: u32,
+    height: u32,
+    samples: u32,
+    colors: Vec<grr::Format>,
+    depth_stencil: Option<grr::Format>,
+    resolve: bool,
+}
+
+impl RenderTargetDesc {
+    pub fn new(width: u32, height: u32) -> RenderTargetDesc {
+        RenderTargetDesc {
+            width,
+            height,
+            samples: 1,
+            colors: vec![],
+            depth_stencil: None,
+            resolve: false,
+        }
+    }
+
+    /// Add a color attachment. Attachments are numbered in the order
+    /// they are added.
+    pub fn color(mut self, format: grr::Format) -> RenderTargetDesc {
+        self.colors.push(format);
+        self
+    }
+
+    /// Set the depth, stencil or combined depth-stencil attachment.
+    /// The attachment point is chosen from the format.
+    pub fn depth_stencil(self, format: impl Into<Option<grr::Format>>) -> RenderTargetDesc {
+        RenderTargetDesc {
+            depth_stencil: format.into(),
+            ..self
+        }
+    }
+
+    pub fn samples(self, samples: u32) -> RenderTargetDesc {
+        RenderTargetDesc { samples, ..self }
+    }
+
+    /// For multisampled targets, also create a single-sampled target
+    /// with the same attachments to resolve into.
+    pub fn resolve(self, resolve: bool) -> RenderTargetDesc {
+        RenderTargetDesc { resolve, ..self }
+    }
+
+    fn image_type(&self) -> grr::ImageType {
+        grr::ImageType::D2 {
+            width: self.width,
+            height: self.height,
+            layers: 1,
+            samples: self.samples,
+        }
+    }
+}
+
+/// Return the framebuffer attachment point for a depth and/or stencil format.
+fn depth_stencil_attachment(format: grr::Format) -> grr::Attachment {
+    match format.base_format() {
+        grr::BaseFormat::Stencil => grr::Attachment::Stencil,
+        grr::BaseFormat::DepthStencil => grr::Attachment::DepthStencil,
+        _ => grr::Attachment::Depth,
+    }
+}
+
+/// A single attachment of a render target.
+#[derive(Debug, Clone, Copy)]
+struct RenderAttachment {
+    attachment: grr::Attachment,
+    image: ImageId,
+    view: ImageViewId,
+}
+
+/// Framebuffer with a set of managed image attachments.
+///
+/// The framebuffer is created once and re-bound whenever the
+/// attachments are resized. The `ImageId`s of the attachments remain
+/// the same for the lifetime of the target.
+pub struct RenderTarget {
+    desc: RenderTargetDesc,
+    framebuffer: grr::Framebuffer,
+    attachments: Vec<RenderAttachment>,
+
+    /// Single-attachment framebuffers for the secondary color
+    /// attachments, used to blit each of them separately.
+    color_framebuffers: Vec<grr::Framebuffer>,
+
+    resolve_target: Option<Box<RenderTarget>>,
+}
+
+impl RenderTarget {
+    /// Create the attachment images and framebuffer for a render target.
+    ///
+    /// On failure, everything created so far is deleted.
+    pub fn new(images: &mut ImageManager, desc: RenderTargetDesc) -> Result<RenderTarget, Error> {
+        let framebuffer = unsafe { images.device().create_framebuffer()? };
+        let mut rt = RenderTarget {
+            desc,
+            framebuffer,
+            attachments: vec![],
+            color_framebuffers: vec![],
+            resolve_target: None,
+        };
+
+        match rt.create_attachments(images) {
+            Ok(()) => {
+                rt.bind_attachments(images);
+                Ok(rt)
+            }
+            Err(e) => {
+                rt.delete(images);
+                Err(e)
+            }
+        }
+    }
+
+    /// Create the attachments, secondary framebuffers and resolve
+    /// target, adding each to `self` as soon as it exists.
+    fn create_attachments(&mut self, images: &mut ImageManager) -> Result<(), Error> {
+        let image_type = self.desc.image_type();
+        let formats: Vec<_> = self
+            .desc
+            .colors
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (grr::Attachment::Color(i as u32), *f))
+            .chain(
+                self.desc
+                    .depth_stencil
+                    .map(|f| (depth_stencil_attachment(f), f)),
+            )
+            .collect();
+
+        for (attachment, format) in formats {
+            let image = images.create_image(image_type, format, 1)?;
+            let view = match images.create_image_view_whole(image) {
+                Ok(view) => view,
+                Err(e) => {
+                    images.delete_image(image);
+                    return Err(e);
+                }
+            };
+            self.attachments.push(RenderAttachment {
+                attachment,
+                image,
+                view,
+            });
+        }
+
+        for _ in 1..self.desc.colors.len() {
+            let fb = unsafe { images.device().create_framebuffer()? };
+            self.color_framebuffers.push(fb);
+        }
+
+        if self.desc.samples > 1 && self.desc.resolve {
+            let resolve_desc = RenderTargetDesc {
+                samples: 1,
+                resolve: false,
+                ..self.desc.clone()
+            };
+            self.resolve_target = Some(Box::new(RenderTarget::new(images, resolve_desc)?));
+        }
+
+        Ok(())
+    }
+
+    /// Attach the current image views to the framebuffers.
+    fn bind_attachments(&self, images: &ImageManager) {
+        let device = images.device();
+        let views: Vec<_> = self
+            .attachments
+            .iter()
+            .filter_map(|a| {
+                images
+                    .get_image_view_handle(a.view)
+                    .map(|v| (a.attachment, grr::AttachmentView::Image(v)))
+            })
+            .collect();
+
+        let draw_buffers: Vec<u32> = (0..self.desc.colors.len() as u32).collect();
+
+        unsafe {
+            device.bind_attachments(self.framebuffer, &views);
+            device.set_color_attachments(self.framebuffer, &draw_buffers);
+
+            for (fb, &(_, view)) in self.color_framebuffers.iter().zip(views.iter().skip(1)) {
+                device.bind_attachments(*fb, &[(grr::Attachment::Color(0), view)]);
+            }
+        }
+    }
+
+    /// Return the framebuffer for rendering into this target.
+    pub fn framebuffer(&self) -> grr::Framebuffer {
+        self.framebuffer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.desc.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.desc.height
+    }
+
+    /// Return the region covering the whole target.
+    pub fn region(&self) -> grr::Region {
+        grr::Region {
+            x: 0,
+            y: 0,
+            w: self.desc.width as _,
+            h: self.desc.height as _,
+        }
+    }
+
+    /// Return the image bound to color attachment `index`.
+    pub fn color_image(&self, index: u32) -> Option<ImageId> {
+        self.attachment(grr::Attachment::Color(index))
+            .map(|a| a.image)
+    }
+
+    /// Return the view bound to color attachment `index`.
+    pub fn color_view(&self, index: u32) -> Option<ImageViewId> {
+        self.attachment(grr::Attachment::Color(index))
+            .map(|a| a.view)
+    }
+
+    /// Return the image bound to the depth, stencil or depth-stencil
+    /// attachment.
+    pub fn depth_stencil_image(&self) -> Option<ImageId> {
+        self.desc
+            .depth_stencil
+            .and_then(|f| self.attachment(depth_stencil_attachment(f)))
+            .map(|a| a.image)
+    }
+
+    /// Return the view bound to the depth, stencil or depth-stencil
+    /// attachment.
+    pub fn depth_stencil_view(&self) -> Option<ImageViewId> {
+        self.desc
+            .depth_stencil
+            .and_then(|f| self.attachment(depth_stencil_attachment(f)))
+            .map(|a| a.view)
+    }
+
+    fn attachment(&self, attachment: grr::Attachment) -> Option<&RenderAttachment> {
+        self.attachments
+            .iter()
+            .find(|a| a.attachment == attachment)
+    }
+
+    /// Return the single-sampled target that this target resolves
+    /// into, if one was requested.
+    pub fn resolve_target(&self) -> Option<&RenderTarget> {
+        self.resolve_target.as_deref()
+    }
+
+    /// Resize every attachment (and the resolve target), e.g. after a
+    /// window resize. The contents of the attachments are undefined
+    /// afterwards.
+    ///
+    /// Resizing to an empty size, as reported for minimized windows,
+    /// is ignored and keeps the current attachments. The new storage
+    /// of every attachment is allocated before any is swapped in, so
+    /// on failure the target is left unchanged.
+    pub fn resize(&mut self, images: &mut ImageManager, width: u32, height: u32) -> Result<(), Error> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        if width == self.desc.width && height == self.desc.height {
+            return Ok(());
+        }
+
+        let mut pending = vec![];
+        if let Err(e) = self.prepare_resize(images, width, height, &mut pending) {
+            for p in pending {
+                images.discard_storage(p);
+            }
+            return Err(e);
+        }
+        for p in pending {
+            images.commit_storage(p);
+        }
+        self.finish_resize(images, width, height);
+
+        Ok(())
+    }
+
+    /// Allocate resized storage for the attachments of this target
+    /// and its resolve target.
+    fn prepare_resize(
+        &self,
+        images: &ImageManager,
+        width: u32,
+        height: u32,
+        pending: &mut Vec<PendingStorage>,
+    ) -> Result<(), Error> {
+        let image_type = grr::ImageType::D2 {
+            width,
+            height,
+            layers: 1,
+            samples: self.desc.samples,
+        };
+        for a in &self.attachments {
+            pending.push(images.prepare_reallocation(a.image, image_type)?);
+        }
+        if let Some(rt) = self.resolve_target.as_ref() {
+            rt.prepare_resize(images, width, height, pending)?;
+        }
+        Ok(())
+    }
+
+    /// Record the new size and attach the new views, once the
+    /// storage has been swapped in.
+    fn finish_resize(&mut self, images: &ImageManager, width: u32, height: u32) {
+        self.desc.width = width;
+        self.desc.height = height;
+        self.bind_attachments(images);
+        if let Some(rt) = self.resolve_target.as_mut() {
+            rt.finish_resize(images, width, height);
+        }
+    }
+
+    /// Blit every color attachment into the resolve target. Depth and
+    /// stencil attachments are not resolved.
+    ///
+    /// Does nothing if the target was not created with a resolve target.
+    pub fn resolve(&self, device: &grr::Device) {
+        let dst = match self.resolve_target.as_ref() {
+            Some(rt) => rt,
+            None => return,
+        };
+
+        let region = self.region();
+        unsafe {
+            // color attachment 0
+            device.blit(
+                self.framebuffer,
+                region,
+                dst.framebuffer,
+                region,
+                grr::Filter::Nearest,
+            );
+            for (src_fb, dst_fb) in self
+                .color_framebuffers
+                .iter()
+                .zip(dst.color_framebuffers.iter())
+            {
+                device.blit(*src_fb, region, *dst_fb, region, grr::Filter::Nearest);
+            }
+        }
+    }
+
+    /// Blit the first color attachment (resolving it, if
+    /// multisampled) into a region of another framebuffer, such as
+    /// `grr::Framebuffer::DEFAULT`.
+    pub fn blit_to(
+        &self,
+        device: &grr::Device,
+        dst: grr::Framebuffer,
+        dst_region: grr::Region,
+        filter: grr::Filter,
+    ) {
+        unsafe {
+            device.blit(self.framebuffer, self.region(), dst, dst_region, filter);
+        }
+    }
+
+    /// Delete the framebuffers and every attachment image and view.
+    pub fn delete(self, images: &mut ImageManager) {
+        let device = images.device();
+        unsafe {
+            device.delete_framebuffer(self.framebuffer);
+            for fb in &self.color_framebuffers {
+                device.delete_framebuffer(*fb);
+            }
+        }
+        for a in &self.attachments {
+            images.delete_image_view(a.view);
+            images.delete_image(a.image);
+        }
+        if let Some(rt) = self.resolve_target {
+            rt.delete(images);
+        }
+    }
+}
@@ -1,101 +1,598 @@
 //! Capture screenshots from a framebuffer or texture to disk.
+use crate::image_format::{base_format_num_components, TexelBaseType};
+use crate::raw_gl::{self, require_gl};
 use std::path::Path;
+use thiserror::Error;
 
-/// Save the color information for the default framebuffer to disk.
-pub fn save_framebuffer_rgba<P: AsRef<Path>>(
-    src_framebuffer: grr::Framebuffer,
-    device: &grr::Device,
-    region: grr::Region,
+/// File format used when writing images to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFileFormat {
+    /// 8-bit per channel RGBA PNG.
+    Png,
+    /// 16-bit per channel RGBA PNG.
+    Png16,
+    /// Radiance HDR. Alpha is discarded.
+    Hdr,
+    /// OpenEXR, with 32-bit float channels.
+    Exr,
+}
+
+/// Mapping applied to float data before it is quantized into a
+/// fixed-point format.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapping {
+    /// Clamp values to [0, 1].
+    Clamp,
+    /// Reinhard operator, `x / (1 + x)`.
+    Reinhard,
+    /// Exponential exposure curve, `1 - exp(-exposure * x)`.
+    Exposure(f32),
+}
+
+impl ToneMapping {
+    /// Map a single (color) value into [0, 1].
+    pub fn apply(self, x: f32) -> f32 {
+        let y = match self {
+            ToneMapping::Clamp => x,
+            ToneMapping::Reinhard => x / (1.0 + x.max(0.0)),
+            ToneMapping::Exposure(e) => 1.0 - (-e * x).exp(),
+        };
+        y.max(0.0).min(1.0)
+    }
+}
+
+/// Reverse the order of the rows of a packed image in place.
+pub fn flip_rows<T>(data: &mut [T], row_len: usize, num_rows: usize) {
+    for i in 0..num_rows / 2 {
+        let (top, bottom) = data.split_at_mut((num_rows - 1 - i) * row_len);
+        top[i * row_len..(i + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+    }
+}
+
+/// Save packed RGBA float data to disk.
+///
+/// Tone mapping is applied to the color channels only when writing
+/// to a fixed-point format; alpha is always clamped. HDR and EXR
+/// receive the data unchanged.
+pub fn save_rgba_f32<P: AsRef<Path>>(
     path: P,
-) -> Result<(), std::io::Error> {
-    let pixel_data_size: usize = (region.w * region.h) as usize * 4;
+    data: &[f32],
+    width: u32,
+    height: u32,
+    file_format: ImageFileFormat,
+    tone_mapping: ToneMapping,
+) -> image::ImageResult<()> {
+    if data.len() as u64 != width as u64 * height as u64 * 4 {
+        return Err(dimension_mismatch());
+    }
 
-    // create a non-multisampled framebuffer to blit to.
-    let (img, img_view) = unsafe {
-        device
-            .create_image_and_view(
+    let quantize = |i: usize, x: f32, max: f32| {
+        let y = if i % 4 == 3 {
+            x.max(0.0).min(1.0)
+        } else {
+            tone_mapping.apply(x)
+        };
+        (y * max).round()
+    };
+
+    match file_format {
+        ImageFileFormat::Png => {
+            let buf: Vec<u8> = data
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| quantize(i, x, 255.0) as u8)
+                .collect();
+            image::save_buffer(path, &buf, width, height, image::ColorType::Rgba8)
+        }
+        ImageFileFormat::Png16 => {
+            let buf: Vec<u16> = data
+                .iter()
+                .enumerate()
+                .map(|(i, &x)| quantize(i, x, 65535.0) as u16)
+                .collect();
+            let img: image::ImageBuffer<image::Rgba<u16>, _> =
+                image::ImageBuffer::from_raw(width, height, buf).ok_or_else(dimension_mismatch)?;
+            img.save_with_format(path, image::ImageFormat::Png)
+        }
+        ImageFileFormat::Hdr => {
+            let pixels: Vec<image::Rgb<f32>> = data
+                .chunks_exact(4)
+                .map(|c| image::Rgb([c[0], c[1], c[2]]))
+                .collect();
+            let f = std::io::BufWriter::new(std::fs::File::create(path)?);
+            image::codecs::hdr::HdrEncoder::new(f).encode(
+                &pixels,
+                width as usize,
+                height as usize,
+            )
+        }
+        ImageFileFormat::Exr => {
+            let img: image::ImageBuffer<image::Rgba<f32>, _> =
+                image::ImageBuffer::from_raw(width, height, data.to_vec())
+                    .ok_or_else(dimension_mismatch)?;
+            img.save_with_format(path, image::ImageFormat::OpenExr)
+        }
+    }
+}
+
+/// Error for pixel data that does not match the image dimensions.
+fn dimension_mismatch() -> image::ImageError {
+    image::ImageError::Parameter(image::error::ParameterError::from_kind(
+        image::error::ParameterErrorKind::DimensionMismatch,
+    ))
+}
+
+/// Errors from capturing or saving a screenshot.
+#[derive(Error, Debug)]
+pub enum ScreenshotError {
+    #[error("grr error")]
+    Grr(#[from] grr::Error),
+
+    #[error("failed to encode screenshot")]
+    Encode(#[from] image::ImageError),
+
+    #[error("invalid capture region {0:?}")]
+    InvalidRegion(grr::Region),
+
+    #[error("framebuffer has no color attachment {0}")]
+    InvalidAttachment(u32),
+
+    #[error("failed to write captured pixels")]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    GlNotLoaded(#[from] raw_gl::NotLoaded),
+}
+
+/// Captured pixels, as tightly packed RGBA rows from top to bottom.
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    /// Write the pixels to disk, with the format chosen from the
+    /// extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ScreenshotError> {
+        image::save_buffer(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )?;
+        Ok(())
+    }
+}
+
+/// Temporary objects used for a capture, deleted when dropped so
+/// that every exit path cleans up.
+pub(crate) struct Staging<'d> {
+    device: &'d grr::Device,
+    image: Option<grr::Image>,
+    view: Option<grr::ImageView>,
+    framebuffer: Option<grr::Framebuffer>,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+}
+
+impl<'d> Staging<'d> {
+    /// Create a non-multisampled image of `format` and a framebuffer
+    /// with it as color attachment 0.
+    pub(crate) fn create(
+        device: &'d grr::Device,
+        width: u32,
+        height: u32,
+        format: grr::Format,
+    ) -> Result<Self, ScreenshotError> {
+        let mut staging = Staging {
+            device,
+            image: None,
+            view: None,
+            framebuffer: None,
+            width,
+            height,
+        };
+        unsafe {
+            let (img, img_view) = device.create_image_and_view(
                 grr::ImageType::D2 {
-                    width: region.w as u32,
-                    height: region.h as u32,
+                    width,
+                    height,
                     layers: 1,
                     samples: 1,
                 },
-                grr::Format::R8G8B8A8_SRGB,
+                format,
                 1,
-            )
-            .unwrap()
-    };
+            )?;
+            staging.image = Some(img);
+            staging.view = Some(img_view);
 
-    let fb = unsafe { device.create_framebuffer().unwrap() };
-    unsafe {
-        device.bind_attachments(
-            fb,
-            &[(
-                grr::Attachment::Color(0),
-                grr::AttachmentView::Image(img_view),
-            )],
-        );
+            let fb = device.create_framebuffer()?;
+            staging.framebuffer = Some(fb);
+            device.bind_attachments(
+                fb,
+                &[(
+                    grr::Attachment::Color(0),
+                    grr::AttachmentView::Image(img_view),
+                )],
+            );
+        }
+        Ok(staging)
+    }
+
+    pub(crate) fn framebuffer(&self) -> grr::Framebuffer {
+        self.framebuffer.expect("staging framebuffer is created")
+    }
+
+    /// Blit color attachment `index` of `src_framebuffer` into the
+    /// staging image, resolving multisampled framebuffers.
+    ///
+    /// The read buffer of `src_framebuffer` is restored afterwards.
+    pub(crate) fn blit_from(
+        &self,
+        src_framebuffer: grr::Framebuffer,
+        region: grr::Region,
+        index: u32,
+    ) -> Result<(), ScreenshotError> {
+        let src_raw = grr::Object::handle(&src_framebuffer);
+        if index > 0 && src_raw == 0 {
+            return Err(ScreenshotError::InvalidAttachment(index));
+        }
+
+        let prev_read_buffer = if src_raw != 0 {
+            require_gl!(
+                GetIntegerv,
+                BindFramebuffer,
+                GetNamedFramebufferAttachmentParameteriv,
+                NamedFramebufferReadBuffer,
+            )?;
+            if !unsafe { has_color_attachment(src_raw, index) } {
+                return Err(ScreenshotError::InvalidAttachment(index));
+            }
+            let prev = unsafe { read_buffer(src_raw) };
+            unsafe {
+                gl::NamedFramebufferReadBuffer(src_raw, gl::COLOR_ATTACHMENT0 + index);
+            }
+            Some(prev)
+        } else {
+            None
+        };
+
+        unsafe {
+            self.device.blit(
+                src_framebuffer,
+                region,
+                self.framebuffer(),
+                grr::Region {
+                    x: 0,
+                    y: 0,
+                    w: self.width as _,
+                    h: self.height as _,
+                },
+                grr::Filter::Nearest,
+            );
+            if let Some(prev) = prev_read_buffer {
+                gl::NamedFramebufferReadBuffer(src_raw, prev);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return whether framebuffer object `fb` has an image attached to
+/// color attachment `index`.
+unsafe fn has_color_attachment(fb: u32, index: u32) -> bool {
+    let mut max = 0;
+    gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max);
+    if index >= max as u32 {
+        return false;
+    }
+
+    let mut ty = 0;
+    gl::GetNamedFramebufferAttachmentParameteriv(
+        fb,
+        gl::COLOR_ATTACHMENT0 + index,
+        gl::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE,
+        &mut ty,
+    );
+    ty as u32 != gl::NONE
+}
+
+/// Return the read buffer of framebuffer object `fb`, keeping the
+/// current read framebuffer binding.
+unsafe fn read_buffer(fb: u32) -> u32 {
+    let mut bound = 0;
+    gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut bound);
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fb);
+    let mut buffer = 0;
+    gl::GetIntegerv(gl::READ_BUFFER, &mut buffer);
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, bound as u32);
+    buffer as u32
+}
+
+impl<'d> Drop for Staging<'d> {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(fb) = self.framebuffer.take() {
+                self.device.delete_framebuffer(fb);
+            }
+            if let Some(view) = self.view.take() {
+                self.device.delete_image_view(view);
+            }
+            if let Some(image) = self.image.take() {
+                self.device.delete_image(image);
+            }
+        }
+    }
+}
+
+/// Blit a color attachment of a framebuffer into a temporary
+/// non-multisampled image of `format`, and read it into memory.
+///
+/// Returns packed rows from top to bottom, with `base_format`
+/// components of `T` per pixel.
+fn read_color_attachment<T: TexelBaseType>(
+    src_framebuffer: grr::Framebuffer,
+    device: &grr::Device,
+    region: grr::Region,
+    index: u32,
+    format: grr::Format,
+    base_format: grr::BaseFormat,
+) -> Result<Vec<T>, ScreenshotError> {
+    if region.w <= 0 || region.h <= 0 {
+        return Err(ScreenshotError::InvalidRegion(region));
     }
+    let (width, height) = (region.w as u32, region.h as u32);
+    let dst_region = grr::Region {
+        x: 0,
+        y: 0,
+        w: region.w,
+        h: region.h,
+    };
+
+    // create a non-multisampled framebuffer to blit to.
+    let staging = Staging::create(device, width, height, format)?;
+    staging.blit_from(src_framebuffer, region, index)?;
 
     // create a buffer to read the results to.
-    let mut buffer_data: Vec<u8> = Vec::with_capacity(pixel_data_size);
-    buffer_data.resize(pixel_data_size, 0);
+    let mut data = vec![
+        T::zero();
+        width as usize * height as usize * base_format_num_components(base_format)
+    ];
 
     unsafe {
-        // blit to the target, non-multisample buffer
-        device.blit(
-            src_framebuffer,
-            region,
-            fb,
-            grr::Region {
-                x: 0,
-                y: 0,
-                w: region.w,
-                h: region.h,
-            },
-            grr::Filter::Linear,
-        );
-
-        device.bind_read_framebuffer(fb);
+        device.bind_read_framebuffer(staging.framebuffer());
 
         let memory_layout = grr::MemoryLayout {
             alignment: 1,
-            base_format: grr::BaseFormat::RGBA,
-            format_layout: grr::FormatLayout::U8,
+            base_format,
+            format_layout: T::layout,
             row_length: 0,
             image_height: 0,
         };
 
         // copy from that framebuffer to host memory
-        device.copy_attachment_to_host(region, memory_layout, &mut buffer_data);
+        device.copy_attachment_to_host(dst_region, memory_layout, &mut data);
     }
 
     // Swap the order of the rows so that the output will not be flipped vertically.
-    for i in 0..region.h / 2 {
-        let r1_offset = (i * region.w * 4) as usize;
-        let r2_offset = ((region.h - 1 - i) * region.w * 4) as usize;
-        unsafe {
-            std::ptr::swap_nonoverlapping(
-                buffer_data.as_mut_ptr().add(r1_offset),
-                buffer_data.as_mut_ptr().add(r2_offset),
-                (region.w * 4) as usize,
-            );
+    let row_len = width as usize * base_format_num_components(base_format);
+    flip_rows(&mut data, row_len, height as usize);
+
+    Ok(data)
+}
+
+/// Read the color information of a region of a framebuffer into
+/// memory.
+///
+/// The region is first blitted into a temporary non-multisampled
+/// sRGB image, so multisampled framebuffers are resolved.
+pub fn capture_framebuffer_rgba(
+    src_framebuffer: grr::Framebuffer,
+    device: &grr::Device,
+    region: grr::Region,
+) -> Result<Screenshot, ScreenshotError> {
+    capture_attachment_rgba(src_framebuffer, device, region, 0)
+}
+
+/// Read color attachment `index` of a framebuffer into memory as
+/// 8-bit sRGB.
+///
+/// Only attachment 0 is available on the default framebuffer.
+pub fn capture_attachment_rgba(
+    src_framebuffer: grr::Framebuffer,
+    device: &grr::Device,
+    region: grr::Region,
+    index: u32,
+) -> Result<Screenshot, ScreenshotError> {
+    let pixels = read_color_attachment::<u8>(
+        src_framebuffer,
+        device,
+        region,
+        index,
+        grr::Format::R8G8B8A8_SRGB,
+        grr::BaseFormat::RGBA,
+    )?;
+
+    Ok(Screenshot {
+        width: region.w as u32,
+        height: region.h as u32,
+        pixels,
+    })
+}
+
+/// Captured float pixels, as tightly packed RGBA rows from top to
+/// bottom.
+#[derive(Debug, Clone)]
+pub struct FloatScreenshot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<f32>,
+}
+
+impl FloatScreenshot {
+    /// Write the pixels to disk. Tone mapping only applies to PNG
+    /// output.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_format: ImageFileFormat,
+        tone_mapping: ToneMapping,
+    ) -> Result<(), ScreenshotError> {
+        save_rgba_f32(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            file_format,
+            tone_mapping,
+        )?;
+        Ok(())
+    }
+}
+
+/// Read color attachment `index` of a framebuffer into memory as
+/// 32-bit floats, keeping values outside of [0, 1] in float
+/// attachments.
+pub fn capture_attachment_f32(
+    src_framebuffer: grr::Framebuffer,
+    device: &grr::Device,
+    region: grr::Region,
+    index: u32,
+) -> Result<FloatScreenshot, ScreenshotError> {
+    let pixels = read_color_attachment::<f32>(
+        src_framebuffer,
+        device,
+        region,
+        index,
+        grr::Format::R32G32B32A32_SFLOAT,
+        grr::BaseFormat::RGBA,
+    )?;
+
+    Ok(FloatScreenshot {
+        width: region.w as u32,
+        height: region.h as u32,
+        pixels,
+    })
+}
+
+/// Captured depth values, as tightly packed rows from top to bottom.
+#[derive(Debug, Clone)]
+pub struct DepthScreenshot {
+    pub width: u32,
+    pub height: u32,
+    pub depth: Vec<f32>,
+}
+
+impl DepthScreenshot {
+    /// Convert window-space depth from a perspective projection with
+    /// the given near and far planes into linear depth, remapped so
+    /// that `near` is 0 and `far` is 1.
+    pub fn linearize(&mut self, near: f32, far: f32) {
+        for d in &mut self.depth {
+            let z = 2.0 * *d - 1.0;
+            let linear = 2.0 * near * far / (far + near - z * (far - near));
+            *d = (linear - near) / (far - near);
         }
     }
 
-    image::save_buffer(
-        path,
-        &buffer_data,
-        region.w as u32,
-        region.h as u32,
-        image::ColorType::Rgba8,
-    )
-    .unwrap();
+    /// Write the depth to disk as a grayscale image.
+    ///
+    /// PNG formats store depth clamped to [0, 1]; HDR and EXR store
+    /// it unchanged.
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        path: P,
+        file_format: ImageFileFormat,
+    ) -> Result<(), ScreenshotError> {
+        if self.depth.len() as u64 != self.width as u64 * self.height as u64 {
+            return Err(dimension_mismatch().into());
+        }
+
+        let clamped = || self.depth.iter().map(|d| d.max(0.0).min(1.0));
+        match file_format {
+            ImageFileFormat::Png => {
+                let buf: Vec<u8> = clamped().map(|d| (d * 255.0).round() as u8).collect();
+                image::save_buffer(path, &buf, self.width, self.height, image::ColorType::L8)?;
+            }
+            ImageFileFormat::Png16 => {
+                let buf: Vec<u16> = clamped().map(|d| (d * 65535.0).round() as u16).collect();
+                let img: image::ImageBuffer<image::Luma<u16>, _> =
+                    image::ImageBuffer::from_raw(self.width, self.height, buf)
+                        .ok_or_else(dimension_mismatch)?;
+                img.save_with_format(path, image::ImageFormat::Png)?;
+            }
+            ImageFileFormat::Hdr | ImageFileFormat::Exr => {
+                let mut rgba = Vec::with_capacity(self.depth.len() * 4);
+                for &d in &self.depth {
+                    rgba.extend_from_slice(&[d, d, d, 1.0]);
+                }
+                save_rgba_f32(
+                    path,
+                    &rgba,
+                    self.width,
+                    self.height,
+                    file_format,
+                    ToneMapping::Clamp,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Read the depth buffer of a framebuffer into memory.
+///
+/// Depth is read directly from `src_framebuffer`, so multisampled
+/// depth buffers must be resolved first. If `near_far` is given, the
+/// depth is linearized with `DepthScreenshot::linearize`.
+pub fn capture_depth(
+    src_framebuffer: grr::Framebuffer,
+    device: &grr::Device,
+    region: grr::Region,
+    near_far: Option<(f32, f32)>,
+) -> Result<DepthScreenshot, ScreenshotError> {
+    if region.w <= 0 || region.h <= 0 {
+        return Err(ScreenshotError::InvalidRegion(region));
+    }
+    let (width, height) = (region.w as u32, region.h as u32);
+    let mut depth = vec![0.0f32; width as usize * height as usize];
 
     unsafe {
-        device.delete_framebuffer(fb);
-        device.delete_image_view(img_view);
-        device.delete_image(img);
+        device.bind_read_framebuffer(src_framebuffer);
+        device.copy_attachment_to_host(
+            region,
+            grr::MemoryLayout {
+                alignment: 1,
+                base_format: grr::BaseFormat::Depth,
+                format_layout: grr::FormatLayout::F32,
+                row_length: 0,
+                image_height: 0,
+            },
+            &mut depth,
+        );
+    }
+    flip_rows(&mut depth, width as usize, height as usize);
+
+    let mut shot = DepthScreenshot {
+        width,
+        height,
+        depth,
+    };
+    if let Some((near, far)) = near_far {
+        shot.linearize(near, far);
     }
-    Ok(())
+    Ok(shot)
+}
+
+/// Save the color information for the default framebuffer to disk.
+pub fn save_framebuffer_rgba<P: AsRef<Path>>(
+    src_framebuffer: grr::Framebuffer,
+    device: &grr::Device,
+    region: grr::Region,
+    path: P,
+) -> Result<(), ScreenshotError> {
+    capture_framebuffer_rgba(src_framebuffer, device, region)?.save(path)
 }
@@ -0,0 +1,521 @@
+//! Parsing of KTX2 and DDS texture containers.
+//!
+//! Parsing works purely on bytes; uploading the result is done by
+//! `ImageManager::load_texture_container`.
+use std::convert::TryInto;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ContainerError {
+    #[error("unrecognized texture container")]
+    UnknownContainer,
+
+    #[error("texture container is truncated or malformed")]
+    Malformed,
+
+    #[error("unsupported texture format {0}")]
+    UnsupportedFormat(String),
+
+    #[error("unsupported container feature: {0}")]
+    UnsupportedFeature(&'static str),
+
+    #[error("could not read texture container")]
+    Io(#[from] std::io::Error),
+}
+
+/// Compressed format families that the device is able to sample from.
+///
+/// Every family is core in OpenGL 4.5 except S3TC (BC1-3), which is
+/// a near-universal extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionSupport {
+    /// BC1 through BC7.
+    pub bc: bool,
+    /// ETC2 and EAC.
+    pub etc2: bool,
+}
+
+impl Default for CompressionSupport {
+    fn default() -> CompressionSupport {
+        CompressionSupport {
+            bc: true,
+            etc2: true,
+        }
+    }
+}
+
+/// Images and metadata decoded from a texture container.
+#[derive(Debug, Clone)]
+pub struct TextureContainer {
+    pub format: grr::Format,
+    pub width: u32,
+    pub height: u32,
+
+    /// Depth of 3D textures; 1 otherwise.
+    pub depth: u32,
+
+    /// Number of array layers; 1 for non-array textures.
+    pub num_layers: u32,
+
+    /// 6 for cube maps, 1 otherwise.
+    pub num_faces: u32,
+
+    /// Texel data of each mipmap level, starting at level 0. Each
+    /// level holds every layer and face, in layer-major order.
+    pub levels: Vec<Vec<u8>>,
+
+    /// Set when the container asks for mipmaps to be generated after
+    /// loading.
+    pub generate_mipmaps: bool,
+}
+
+impl TextureContainer {
+    /// Parse either a KTX2 or DDS container, based on its signature.
+    pub fn parse(bytes: &[u8]) -> Result<TextureContainer, ContainerError> {
+        if bytes.starts_with(&KTX2_IDENTIFIER) {
+            parse_ktx2(bytes)
+        } else if bytes.starts_with(DDS_MAGIC) {
+            parse_dds(bytes)
+        } else {
+            Err(ContainerError::UnknownContainer)
+        }
+    }
+
+    pub fn is_cube(&self) -> bool {
+        self.num_faces == 6
+    }
+
+    /// Return the number of bytes `levels[level]` must hold, covering
+    /// every layer and face, or `None` if it does not fit in `usize`
+    /// or the size of the format is not known.
+    pub fn expected_level_size(&self, level: u32) -> Option<usize> {
+        let num_images = self.num_layers.checked_mul(self.num_faces)?;
+        level_size(self.format, self.width, self.height, self.depth, level)?
+            .checked_mul(num_images as usize)
+    }
+
+    /// Return the image type that holds every layer and face.
+    pub fn image_type(&self) -> grr::ImageType {
+        if self.depth > 1 {
+            grr::ImageType::D3 {
+                width: self.width,
+                height: self.height,
+                depth: self.depth,
+            }
+        } else if self.height > 1 || self.is_cube() {
+            grr::ImageType::D2 {
+                width: self.width,
+                height: self.height,
+                layers: self.num_layers * self.num_faces,
+                samples: 1,
+            }
+        } else {
+            grr::ImageType::D1 {
+                width: self.width,
+                layers: self.num_layers,
+            }
+        }
+    }
+
+    /// Check that the format can be used given the compression support.
+    pub fn check_support(&self, support: CompressionSupport) -> Result<(), ContainerError> {
+        let supported = match compression_family(self.format) {
+            Some(CompressionFamily::Bc) => support.bc,
+            Some(CompressionFamily::Etc2) => support.etc2,
+            None => true,
+        };
+        if supported {
+            Ok(())
+        } else {
+            Err(ContainerError::UnsupportedFormat(format!(
+                "{:?} (not supported by the device)",
+                self.format
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompressionFamily {
+    Bc,
+    Etc2,
+}
+
+fn compression_family(format: grr::Format) -> Option<CompressionFamily> {
+    use grr::Format::*;
+    match format {
+        BC1_RGB_UNORM_BLOCK | BC1_RGB_SRGB_BLOCK | BC1_RGBA_UNORM_BLOCK | BC1_RGBA_SRGB_BLOCK
+        | BC2_UNORM_BLOCK | BC2_SRGB_BLOCK | BC3_UNORM_BLOCK | BC3_SRGB_BLOCK
+        | BC4_UNORM_BLOCK | BC4_SNORM_BLOCK | BC5_UNORM_BLOCK | BC5_SNORM_BLOCK
+        | BC6H_UFLOAT_BLOCK | BC6H_SFLOAT_BLOCK | BC7_UNORM_BLOCK | BC7_SRGB_BLOCK => {
+            Some(CompressionFamily::Bc)
+        }
+        ETC2_R8G8B8_UNORM_BLOCK | ETC2_R8G8B8_SRGB_BLOCK | ETC2_R8G8B8A1_UNORM_BLOCK
+        | ETC2_R8G8B8A1_SRGB_BLOCK | ETC2_R8G8B8A8_UNORM_BLOCK | ETC2_R8G8B8A8_SRGB_BLOCK
+        | EAC_R11_UNORM_BLOCK | EAC_R11_SNORM_BLOCK | EAC_R11G11_UNORM_BLOCK
+        | EAC_R11G11_SNORM_BLOCK => Some(CompressionFamily::Etc2),
+        _ => None,
+    }
+}
+
+/// Return true if the format is block-compressed.
+pub fn is_compressed(format: grr::Format) -> bool {
+    compression_family(format).is_some()
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, ContainerError> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(ContainerError::Malformed)
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, ContainerError> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(ContainerError::Malformed)
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], ContainerError> {
+    bytes
+        .get(offset..offset.checked_add(len).ok_or(ContainerError::Malformed)?)
+        .ok_or(ContainerError::Malformed)
+}
+
+/// Swap the red and blue channels of packed 8-bit BGRA data.
+fn bgra_to_rgba(data: &mut [u8]) {
+    for texel in data.chunks_exact_mut(4) {
+        texel.swap(0, 2);
+    }
+}
+
+pub const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// Map a `VkFormat` value to a format, and whether the data is
+/// stored as BGRA.
+fn vk_format(vk: u32) -> Option<(grr::Format, bool)> {
+    use grr::Format::*;
+    let f = match vk {
+        9 => R8_UNORM,
+        16 => R8G8_UNORM,
+        37 => R8G8B8A8_UNORM,
+        43 => R8G8B8A8_SRGB,
+        44 => return Some((R8G8B8A8_UNORM, true)),
+        50 => return Some((R8G8B8A8_SRGB, true)),
+        70 => R16_UNORM,
+        76 => R16_SFLOAT,
+        83 => R16G16_SFLOAT,
+        91 => R16G16B16A16_UNORM,
+        97 => R16G16B16A16_SFLOAT,
+        100 => R32_SFLOAT,
+        103 => R32G32_SFLOAT,
+        106 => R32G32B32_SFLOAT,
+        109 => R32G32B32A32_SFLOAT,
+        131 => BC1_RGB_UNORM_BLOCK,
+        132 => BC1_RGB_SRGB_BLOCK,
+        133 => BC1_RGBA_UNORM_BLOCK,
+        134 => BC1_RGBA_SRGB_BLOCK,
+        135 => BC2_UNORM_BLOCK,
+        136 => BC2_SRGB_BLOCK,
+        137 => BC3_UNORM_BLOCK,
+        138 => BC3_SRGB_BLOCK,
+        139 => BC4_UNORM_BLOCK,
+        140 => BC4_SNORM_BLOCK,
+        141 => BC5_UNORM_BLOCK,
+        142 => BC5_SNORM_BLOCK,
+        143 => BC6H_UFLOAT_BLOCK,
+        144 => BC6H_SFLOAT_BLOCK,
+        145 => BC7_UNORM_BLOCK,
+        146 => BC7_SRGB_BLOCK,
+        147 => ETC2_R8G8B8_UNORM_BLOCK,
+        148 => ETC2_R8G8B8_SRGB_BLOCK,
+        149 => ETC2_R8G8B8A1_UNORM_BLOCK,
+        150 => ETC2_R8G8B8A1_SRGB_BLOCK,
+        151 => ETC2_R8G8B8A8_UNORM_BLOCK,
+        152 => ETC2_R8G8B8A8_SRGB_BLOCK,
+        153 => EAC_R11_UNORM_BLOCK,
+        154 => EAC_R11_SNORM_BLOCK,
+        155 => EAC_R11G11_UNORM_BLOCK,
+        156 => EAC_R11G11_SNORM_BLOCK,
+        _ => return None,
+    };
+    Some((f, false))
+}
+
+/// Parse a KTX2 container.
+///
+/// Supercompressed containers are not supported.
+pub fn parse_ktx2(bytes: &[u8]) -> Result<TextureContainer, ContainerError> {
+    if !bytes.starts_with(&KTX2_IDENTIFIER) {
+        return Err(ContainerError::UnknownContainer);
+    }
+
+    let vk = read_u32(bytes, 12)?;
+    let width = read_u32(bytes, 20)?;
+    let height = read_u32(bytes, 24)?;
+    let depth = read_u32(bytes, 28)?;
+    let layer_count = read_u32(bytes, 32)?;
+    let face_count = read_u32(bytes, 36)?;
+    let level_count = read_u32(bytes, 40)?;
+    let supercompression = read_u32(bytes, 44)?;
+
+    if supercompression != 0 {
+        return Err(ContainerError::UnsupportedFeature("supercompression"));
+    }
+    if face_count != 1 && face_count != 6 {
+        return Err(ContainerError::Malformed);
+    }
+    if width == 0 || level_count > max_mip_levels(width, height, depth) {
+        return Err(ContainerError::Malformed);
+    }
+    let (format, bgra) = vk_format(vk)
+        .ok_or_else(|| ContainerError::UnsupportedFormat(format!("VkFormat {}", vk)))?;
+
+    let mut container = TextureContainer {
+        format,
+        width,
+        height: height.max(1),
+        depth: depth.max(1),
+        num_layers: layer_count.max(1),
+        num_faces: face_count,
+        levels: vec![],
+        generate_mipmaps: level_count == 0,
+    };
+
+    // The level index follows the 80-byte header and section index.
+    for level in 0..level_count.max(1) {
+        let entry = 80 + level as usize * 24;
+        let offset = read_u64(bytes, entry)? as usize;
+        let length = read_u64(bytes, entry + 8)? as usize;
+        if container.expected_level_size(level) != Some(length) {
+            return Err(ContainerError::Malformed);
+        }
+        let mut data = slice(bytes, offset, length)?.to_vec();
+        if bgra {
+            bgra_to_rgba(&mut data);
+        }
+        container.levels.push(data);
+    }
+
+    Ok(container)
+}
+
+pub const DDS_MAGIC: &[u8] = b"DDS ";
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE3D: u32 = 4;
+
+fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+/// Map a legacy DDS pixel format to a format, and whether the data
+/// is stored as BGRA.
+fn dds_legacy_format(
+    flags: u32,
+    four_cc: u32,
+    bit_count: u32,
+    masks: [u32; 4],
+) -> Option<(grr::Format, bool)> {
+    use grr::Format::*;
+    if flags & DDPF_FOURCC != 0 {
+        let f = match four_cc {
+            x if x == fourcc(b"DXT1") => BC1_RGBA_UNORM_BLOCK,
+            x if x == fourcc(b"DXT2") || x == fourcc(b"DXT3") => BC2_UNORM_BLOCK,
+            x if x == fourcc(b"DXT4") || x == fourcc(b"DXT5") => BC3_UNORM_BLOCK,
+            x if x == fourcc(b"ATI1") || x == fourcc(b"BC4U") => BC4_UNORM_BLOCK,
+            x if x == fourcc(b"BC4S") => BC4_SNORM_BLOCK,
+            x if x == fourcc(b"ATI2") || x == fourcc(b"BC5U") => BC5_UNORM_BLOCK,
+            x if x == fourcc(b"BC5S") => BC5_SNORM_BLOCK,
+            // D3DFORMAT values stored directly in the FourCC field
+            111 => R16_SFLOAT,
+            112 => R16G16_SFLOAT,
+            113 => R16G16B16A16_SFLOAT,
+            114 => R32_SFLOAT,
+            115 => R32G32_SFLOAT,
+            116 => R32G32B32A32_SFLOAT,
+            _ => return None,
+        };
+        return Some((f, false));
+    }
+
+    if flags & DDPF_RGB != 0 && bit_count == 32 {
+        return match masks {
+            [0xff, 0xff00, 0xff0000, _] => Some((R8G8B8A8_UNORM, false)),
+            [0xff0000, 0xff00, 0xff, _] => Some((R8G8B8A8_UNORM, true)),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+/// Map a `DXGI_FORMAT` value to a format, and whether the data is
+/// stored as BGRA.
+fn dxgi_format(dxgi: u32) -> Option<(grr::Format, bool)> {
+    use grr::Format::*;
+    let f = match dxgi {
+        2 => R32G32B32A32_SFLOAT,
+        6 => R32G32B32_SFLOAT,
+        10 => R16G16B16A16_SFLOAT,
+        11 => R16G16B16A16_UNORM,
+        16 => R32G32_SFLOAT,
+        28 => R8G8B8A8_UNORM,
+        29 => R8G8B8A8_SRGB,
+        34 => R16G16_SFLOAT,
+        41 => R32_SFLOAT,
+        49 => R8G8_UNORM,
+        54 => R16_SFLOAT,
+        56 => R16_UNORM,
+        61 => R8_UNORM,
+        71 => BC1_RGBA_UNORM_BLOCK,
+        72 => BC1_RGBA_SRGB_BLOCK,
+        74 => BC2_UNORM_BLOCK,
+        75 => BC2_SRGB_BLOCK,
+        77 => BC3_UNORM_BLOCK,
+        78 => BC3_SRGB_BLOCK,
+        80 => BC4_UNORM_BLOCK,
+        81 => BC4_SNORM_BLOCK,
+        83 => BC5_UNORM_BLOCK,
+        84 => BC5_SNORM_BLOCK,
+        87 => return Some((R8G8B8A8_UNORM, true)),
+        91 => return Some((R8G8B8A8_SRGB, true)),
+        95 => BC6H_UFLOAT_BLOCK,
+        96 => BC6H_SFLOAT_BLOCK,
+        98 => BC7_UNORM_BLOCK,
+        99 => BC7_SRGB_BLOCK,
+        _ => return None,
+    };
+    Some((f, false))
+}
+
+/// Return the number of bytes in a single image of a mipmap level.
+fn level_size(
+    format: grr::Format,
+    width: u32,
+    height: u32,
+    depth: u32,
+    level: u32,
+) -> Option<usize> {
+    let (bw, bh, bytes) = crate::image_format::format_block_size(format)?;
+    let w = width.checked_shr(level)?.max(1);
+    let h = height.checked_shr(level)?.max(1);
+    let d = depth.checked_shr(level)?.max(1);
+    let blocks_x = ((w + bw - 1) / bw) as usize;
+    let blocks_y = ((h + bh - 1) / bh) as usize;
+    blocks_x
+        .checked_mul(blocks_y)?
+        .checked_mul(d as usize)?
+        .checked_mul(bytes)
+}
+
+/// Return the length of a full mip chain for the given extent.
+fn max_mip_levels(width: u32, height: u32, depth: u32) -> u32 {
+    32 - width.max(height).max(depth).max(1).leading_zeros()
+}
+
+/// Parse a DDS container, including the DX10 header extension.
+pub fn parse_dds(bytes: &[u8]) -> Result<TextureContainer, ContainerError> {
+    if !bytes.starts_with(DDS_MAGIC) {
+        return Err(ContainerError::UnknownContainer);
+    }
+    if read_u32(bytes, 4)? != 124 {
+        return Err(ContainerError::Malformed);
+    }
+
+    let flags = read_u32(bytes, 8)?;
+    let height = read_u32(bytes, 12)?.max(1);
+    let width = read_u32(bytes, 16)?.max(1);
+    let mut depth = read_u32(bytes, 24)?.max(1);
+    let num_levels = if flags & DDSD_MIPMAPCOUNT != 0 {
+        read_u32(bytes, 28)?.max(1)
+    } else {
+        1
+    };
+    if num_levels > max_mip_levels(width, height, depth) {
+        return Err(ContainerError::Malformed);
+    }
+
+    let pf_flags = read_u32(bytes, 80)?;
+    let four_cc = read_u32(bytes, 84)?;
+    let bit_count = read_u32(bytes, 88)?;
+    let masks = [
+        read_u32(bytes, 92)?,
+        read_u32(bytes, 96)?,
+        read_u32(bytes, 100)?,
+        read_u32(bytes, 104)?,
+    ];
+    let caps2 = read_u32(bytes, 112)?;
+
+    let mut num_layers = 1;
+    let mut num_faces = if caps2 & DDSCAPS2_CUBEMAP != 0 { 6 } else { 1 };
+    if caps2 & DDSCAPS2_VOLUME == 0 {
+        depth = 1;
+    }
+
+    let (format, bgra, data_offset) = if pf_flags & DDPF_FOURCC != 0 && four_cc == fourcc(b"DX10")
+    {
+        let dxgi = read_u32(bytes, 128)?;
+        let dimension = read_u32(bytes, 132)?;
+        let misc = read_u32(bytes, 136)?;
+        num_layers = read_u32(bytes, 140)?.max(1);
+        num_faces = if misc & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+            6
+        } else {
+            1
+        };
+        if dimension != DDS_DIMENSION_TEXTURE3D {
+            depth = 1;
+        }
+        let (format, bgra) = dxgi_format(dxgi)
+            .ok_or_else(|| ContainerError::UnsupportedFormat(format!("DXGI_FORMAT {}", dxgi)))?;
+        (format, bgra, 148)
+    } else {
+        let (format, bgra) = dds_legacy_format(pf_flags, four_cc, bit_count, masks)
+            .ok_or_else(|| {
+                ContainerError::UnsupportedFormat(format!(
+                    "DDS pixel format (flags {:#x}, FourCC {:#x})",
+                    pf_flags, four_cc
+                ))
+            })?;
+        (format, bgra, 128)
+    };
+
+    // DDS stores every mip chain of an image contiguously, while the
+    // levels are gathered across images for uploading.
+    let num_images = num_layers
+        .checked_mul(num_faces)
+        .ok_or(ContainerError::Malformed)?;
+    let mut levels = vec![vec![]; num_levels as usize];
+    let mut offset = data_offset;
+    for _ in 0..num_images {
+        for (level, level_data) in levels.iter_mut().enumerate() {
+            let size = level_size(format, width, height, depth, level as u32)
+                .ok_or(ContainerError::Malformed)?;
+            level_data.extend_from_slice(slice(bytes, offset, size)?);
+            offset += size;
+        }
+    }
+    if bgra {
+        levels.iter_mut().for_each(|l| bgra_to_rgba(l));
+    }
+
+    Ok(TextureContainer {
+        format,
+        width,
+        height,
+        depth,
+        num_layers,
+        num_faces,
+        levels,
+        generate_mipmaps: false,
+    })
+}
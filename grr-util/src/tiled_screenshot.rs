@@ -0,0 +1,105 @@
+//! High-resolution screenshots rendered in tiles.
+//!
+//! The image is split into tiles no larger than the framebuffer used
+//! for rendering. Each tile is rendered with a projection narrowed
+//! to its part of the view, captured, and copied into the final
+//! image.
+use crate::screenshot::{capture_framebuffer_rgba, Screenshot, ScreenshotError};
+use nalgebra::Matrix4;
+
+/// Narrow `projection`, used for an image of `width` by `height`
+/// pixels, to the pixels in `tile` (with the origin at the bottom
+/// left, as in GL).
+///
+/// Rendering a tile-sized viewport with the result produces exactly
+/// those pixels of the full image.
+pub fn tile_projection(
+    projection: &Matrix4<f32>,
+    width: u32,
+    height: u32,
+    tile: grr::Region,
+) -> Matrix4<f32> {
+    // Normalized device coordinates of the tile's edges.
+    let x0 = 2.0 * tile.x as f32 / width as f32 - 1.0;
+    let x1 = 2.0 * (tile.x + tile.w) as f32 / width as f32 - 1.0;
+    let y0 = 2.0 * tile.y as f32 / height as f32 - 1.0;
+    let y1 = 2.0 * (tile.y + tile.h) as f32 / height as f32 - 1.0;
+
+    // Map [x0, x1] x [y0, y1] onto [-1, 1]^2 in clip space.
+    let sx = 2.0 / (x1 - x0);
+    let sy = 2.0 / (y1 - y0);
+    #[rustfmt::skip]
+    let crop = Matrix4::new(
+        sx,  0.0, 0.0, -(x1 + x0) / (x1 - x0),
+        0.0, sy,  0.0, -(y1 + y0) / (y1 - y0),
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    );
+    crop * projection
+}
+
+/// Split an image of `width` by `height` pixels into tiles of at most
+/// `tile_width` by `tile_height`, starting at the bottom left.
+pub fn tile_regions(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Vec<grr::Region> {
+    let (tw, th) = (tile_width.max(1), tile_height.max(1));
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(th as usize) {
+        for x in (0..width).step_by(tw as usize) {
+            tiles.push(grr::Region {
+                x: x as _,
+                y: y as _,
+                w: tw.min(width - x) as _,
+                h: th.min(height - y) as _,
+            });
+        }
+    }
+    tiles
+}
+
+/// Render and capture an image of `size` pixels, which may be larger
+/// than any framebuffer, in tiles.
+///
+/// For each tile, `render` is called with the tile's projection and
+/// the viewport region to draw into `framebuffer`, which must be at
+/// least `tile_size`. The tile is then read back and stitched into
+/// the result.
+pub fn capture_tiled<F>(
+    device: &grr::Device,
+    framebuffer: grr::Framebuffer,
+    tile_size: (u32, u32),
+    projection: &Matrix4<f32>,
+    size: (u32, u32),
+    mut render: F,
+) -> Result<Screenshot, ScreenshotError>
+where
+    F: FnMut(&Matrix4<f32>, grr::Region),
+{
+    let (width, height) = size;
+    let (tile_width, tile_height) = tile_size;
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+
+    for tile in tile_regions(width, height, tile_width, tile_height) {
+        let viewport = grr::Region {
+            x: 0,
+            y: 0,
+            w: tile.w,
+            h: tile.h,
+        };
+        render(&tile_projection(projection, width, height, tile), viewport);
+        let shot = capture_framebuffer_rgba(framebuffer, device, viewport)?;
+
+        // Both the capture and the result are stored top to bottom.
+        let row_len = shot.width as usize * 4;
+        let top = (height - tile.y as u32 - shot.height) as usize;
+        for (i, row) in shot.pixels.chunks_exact(row_len).enumerate() {
+            let start = ((top + i) * width as usize + tile.x as usize) * 4;
+            pixels[start..start + row_len].copy_from_slice(row);
+        }
+    }
+
+    Ok(Screenshot {
+        width,
+        height,
+        pixels,
+    })
+}
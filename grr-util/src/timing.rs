@@ -0,0 +1,270 @@
+//! Frame timing: fixed-timestep accumulation and frame-rate caps.
+//!
+//! Time is read through the `Clock` trait, so the logic can be
+//! driven by a `ManualClock` in tests.
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, Copy, PartialEq)]
+pub enum TimingError {
+    #[error("fixed timestep must be positive")]
+    ZeroTimestep,
+
+    #[error("invalid frame rate cap {0}")]
+    InvalidFrameRate(f32),
+}
+
+/// Longest frame accepted by `FrameLimiter`.
+const MAX_FRAME_SECS: f32 = u32::MAX as f32;
+
+/// Source of the current time.
+pub trait Clock {
+    /// Time elapsed since an arbitrary, fixed origin.
+    fn now(&self) -> Duration;
+}
+
+/// Wall-clock time.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> SystemClock {
+        SystemClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> SystemClock {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A clock that only moves when advanced.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ManualClock {
+    pub time: Duration,
+}
+
+impl ManualClock {
+    pub fn advance(&mut self, d: Duration) {
+        self.time += d;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.time
+    }
+}
+
+/// Simulation steps to run for one rendered frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameSteps {
+    /// Number of fixed updates to run.
+    pub steps: u32,
+
+    /// Fraction of a step accumulated past the last update, for
+    /// interpolating between the previous and current state.
+    pub alpha: f32,
+}
+
+/// Fixed-timestep accumulator.
+///
+/// Call `advance` once per frame to learn how many fixed updates to
+/// run. Pausing stops the accumulation of time; `single_step` runs
+/// exactly one update while paused.
+pub struct FixedTimestep<C: Clock = SystemClock> {
+    clock: C,
+    step: Duration,
+    last: Duration,
+    accumulator: Duration,
+    paused: bool,
+    pending_steps: u32,
+
+    /// Upper bound on updates per frame, so that a slow frame does
+    /// not snowball into ever more updates.
+    pub max_steps: u32,
+}
+
+impl FixedTimestep<SystemClock> {
+    /// Run updates every `step`, measured in wall-clock time.
+    ///
+    /// Fails if `step` is zero.
+    pub fn new(step: Duration) -> Result<Self, TimingError> {
+        Self::with_clock(step, SystemClock::new())
+    }
+}
+
+impl<C: Clock> FixedTimestep<C> {
+    /// Run updates every `step`, measured by `clock`.
+    ///
+    /// Fails if `step` is zero.
+    pub fn with_clock(step: Duration, clock: C) -> Result<Self, TimingError> {
+        if step == Duration::from_secs(0) {
+            return Err(TimingError::ZeroTimestep);
+        }
+        let last = clock.now();
+        Ok(FixedTimestep {
+            clock,
+            step,
+            last,
+            accumulator: Duration::from_secs(0),
+            paused: false,
+            pending_steps: 0,
+            max_steps: 8,
+        })
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_paused(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Run one update on the next frame, if paused.
+    pub fn single_step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    /// Account for the time since the last call, and return the
+    /// updates to run this frame.
+    pub fn advance(&mut self) -> FrameSteps {
+        let now = self.clock.now();
+        let elapsed = now.checked_sub(self.last).unwrap_or_default();
+        self.last = now;
+
+        if self.paused {
+            let steps = std::mem::replace(&mut self.pending_steps, 0);
+            return FrameSteps {
+                steps,
+                alpha: self.alpha(),
+            };
+        }
+
+        let max_accumulated = self
+            .step
+            .checked_mul(self.max_steps)
+            .unwrap_or_else(|| Duration::from_secs(u64::MAX));
+        self.accumulator = self
+            .accumulator
+            .checked_add(elapsed)
+            .unwrap_or(max_accumulated)
+            .min(max_accumulated);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+
+        FrameSteps {
+            steps,
+            alpha: self.alpha(),
+        }
+    }
+
+    fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+/// Caps the frame rate by waiting out the rest of each frame.
+pub struct FrameLimiter<C: Clock = SystemClock> {
+    clock: C,
+    min_frame: Option<Duration>,
+    frame_start: Duration,
+}
+
+impl FrameLimiter<SystemClock> {
+    /// Limit to `max_fps` frames per second, or no limit if `None`.
+    ///
+    /// Fails if `max_fps` is not a positive, finite rate with frames
+    /// shorter than `u32::MAX` seconds.
+    pub fn new(max_fps: Option<f32>) -> Result<Self, TimingError> {
+        Self::with_clock(max_fps, SystemClock::new())
+    }
+}
+
+impl<C: Clock> FrameLimiter<C> {
+    /// Limit to `max_fps` frames per second, measured by `clock`. See
+    /// `new`.
+    pub fn with_clock(max_fps: Option<f32>, clock: C) -> Result<Self, TimingError> {
+        let min_frame = match max_fps {
+            Some(fps) => {
+                let secs = 1.0 / fps;
+                // Also rejects NaN.
+                if !(fps > 0.0 && secs <= MAX_FRAME_SECS) {
+                    return Err(TimingError::InvalidFrameRate(fps));
+                }
+                Some(Duration::from_secs_f32(secs))
+            }
+            None => None,
+        };
+        let frame_start = clock.now();
+        Ok(FrameLimiter {
+            clock,
+            min_frame,
+            frame_start,
+        })
+    }
+
+    pub fn clock_mut(&mut self) -> &mut C {
+        &mut self.clock
+    }
+
+    /// Time left before the current frame may end.
+    pub fn remaining(&self) -> Duration {
+        let elapsed = self
+            .clock
+            .now()
+            .checked_sub(self.frame_start)
+            .unwrap_or_default();
+        self.min_frame
+            .and_then(|min| min.checked_sub(elapsed))
+            .unwrap_or_default()
+    }
+
+    /// Start timing the next frame.
+    pub fn next_frame(&mut self) {
+        self.frame_start = self.clock.now();
+    }
+
+    /// Sleep for the rest of the frame, then start the next one.
+    pub fn wait(&mut self) {
+        let remaining = self.remaining();
+        if remaining > Duration::from_secs(0) {
+            std::thread::sleep(remaining);
+        }
+        self.next_frame();
+    }
+}
@@ -1,9 +1,13 @@
+use crate::debug::{self, DebugOutput};
+use crate::raw_gl::{self, require_gl};
 use glutin::dpi::LogicalSize;
 use glutin::event_loop::EventLoop;
+use glutin::monitor::{MonitorHandle, VideoMode};
 use glutin::platform::unix::HeadlessContextExt;
-use glutin::window::WindowBuilder;
-use glutin::{Context, PossiblyCurrent, WindowedContext};
+use glutin::window::{Fullscreen, Icon, WindowBuilder};
+use glutin::{Context, GlProfile, GlRequest, PossiblyCurrent, Robustness, WindowedContext};
 use grr::Device;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -16,6 +20,173 @@ pub enum Error {
 
     #[error("Glutin Context Error")]
     Context(#[from] glutin::ContextError),
+
+    #[error("Raw OpenGL function not loaded")]
+    GlNotLoaded(#[from] raw_gl::NotLoaded),
+
+    #[error("No monitor with index {0}")]
+    NoSuchMonitor(usize),
+
+    #[error("No video mode matches the request")]
+    NoVideoMode,
+
+    #[error("Failed to load window icon")]
+    IconImage(#[from] image::ImageError),
+
+    #[error("Invalid window icon")]
+    BadIcon(#[from] glutin::window::BadIcon),
+
+    #[error("Invalid frame timing")]
+    Timing(#[from] crate::timing::TimingError),
+}
+
+/// Requirements used to pick a video mode for exclusive fullscreen.
+/// Unset fields accept any value; among the matching modes, the
+/// largest, then fastest, then deepest is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VideoModeRequest {
+    pub size: Option<(u32, u32)>,
+    pub refresh_rate: Option<u16>,
+    pub bit_depth: Option<u16>,
+}
+
+impl VideoModeRequest {
+    /// Pick the best matching mode of `monitor`.
+    pub fn select(&self, monitor: &MonitorHandle) -> Option<VideoMode> {
+        monitor
+            .video_modes()
+            .filter(|m| {
+                let size = m.size();
+                self.size.map_or(true, |s| s == (size.width, size.height))
+                    && self.refresh_rate.map_or(true, |r| r == m.refresh_rate())
+                    && self.bit_depth.map_or(true, |b| b == m.bit_depth())
+            })
+            .max_by_key(|m| {
+                let size = m.size();
+                (
+                    size.width as u64 * size.height as u64,
+                    m.refresh_rate(),
+                    m.bit_depth(),
+                )
+            })
+    }
+}
+
+/// How a window is presented. Monitors are given as indices into
+/// the list of available monitors, with `None` meaning the monitor
+/// the window is on (or the primary monitor, before creation).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    /// Borderless window covering a monitor, at its current mode.
+    Borderless { monitor: Option<usize> },
+    /// Exclusive fullscreen, changing the monitor's video mode.
+    Exclusive {
+        monitor: Option<usize>,
+        video_mode: VideoModeRequest,
+    },
+}
+
+impl WindowMode {
+    /// Resolve the mode against the available monitors, with
+    /// `default` standing in for an unspecified monitor.
+    ///
+    /// Exclusive fullscreen falls back to the first monitor when there
+    /// is no default, and fails with `NoSuchMonitor` if there are no
+    /// monitors at all.
+    pub fn fullscreen(
+        &self,
+        monitors: Vec<MonitorHandle>,
+        default: Option<MonitorHandle>,
+    ) -> Result<Option<Fullscreen>, Error> {
+        let pick = |index: Option<usize>| match index {
+            Some(i) => monitors
+                .get(i)
+                .cloned()
+                .map(Some)
+                .ok_or(Error::NoSuchMonitor(i)),
+            None => Ok(default.clone()),
+        };
+
+        match self {
+            WindowMode::Windowed => Ok(None),
+            WindowMode::Borderless { monitor } => Ok(Some(Fullscreen::Borderless(pick(*monitor)?))),
+            WindowMode::Exclusive {
+                monitor,
+                video_mode,
+            } => {
+                let monitor = pick(*monitor)?
+                    .or_else(|| monitors.first().cloned())
+                    .ok_or(Error::NoSuchMonitor(0))?;
+                let mode = video_mode.select(&monitor).ok_or(Error::NoVideoMode)?;
+                Ok(Some(Fullscreen::Exclusive(mode)))
+            }
+        }
+    }
+}
+
+/// Load a window icon from an image file.
+pub fn load_icon<P: AsRef<Path>>(path: P) -> Result<Icon, Error> {
+    let img = image::open(path)?.to_rgba8();
+    let (w, h) = img.dimensions();
+    Ok(Icon::from_rgba(img.into_raw(), w, h)?)
+}
+
+/// Properties of the OpenGL context that was actually created, which
+/// may differ from what was requested.
+#[derive(Debug, Clone)]
+pub struct ContextInfo {
+    pub version: (u32, u32),
+    pub profile: GlProfile,
+    pub forward_compatible: bool,
+    pub debug: bool,
+    pub robust: bool,
+    pub vendor: String,
+    pub renderer: String,
+
+    /// Format of the default framebuffer, for windowed contexts.
+    pub pixel_format: Option<glutin::PixelFormat>,
+}
+
+impl ContextInfo {
+    /// Query the current context.
+    fn query(pixel_format: Option<glutin::PixelFormat>) -> Result<ContextInfo, Error> {
+        require_gl!(GetIntegerv, GetString)?;
+
+        let get = |name| {
+            let mut v = 0;
+            unsafe { gl::GetIntegerv(name, &mut v) };
+            v
+        };
+        let get_string = |name| unsafe {
+            let s = gl::GetString(name);
+            if s.is_null() {
+                String::new()
+            } else {
+                std::ffi::CStr::from_ptr(s as *const _)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+
+        let flags = get(gl::CONTEXT_FLAGS) as u32;
+        let profile = if get(gl::CONTEXT_PROFILE_MASK) as u32 & gl::CONTEXT_CORE_PROFILE_BIT != 0 {
+            GlProfile::Core
+        } else {
+            GlProfile::Compatibility
+        };
+
+        Ok(ContextInfo {
+            version: (get(gl::MAJOR_VERSION) as u32, get(gl::MINOR_VERSION) as u32),
+            profile,
+            forward_compatible: flags & gl::CONTEXT_FLAG_FORWARD_COMPATIBLE_BIT != 0,
+            debug: flags & gl::CONTEXT_FLAG_DEBUG_BIT != 0,
+            robust: flags & gl::CONTEXT_FLAG_ROBUST_ACCESS_BIT != 0,
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            pixel_format,
+        })
+    }
 }
 
 /// Single window with an OpenGL / `grr` device and event loop and OpenGL
@@ -24,14 +195,89 @@ pub struct GrrWindow {
     pub window: WindowedContext<PossiblyCurrent>,
     pub event_loop: EventLoop<()>,
     pub device: Device,
+    pub info: ContextInfo,
+
+    /// Mode restored by `toggle_fullscreen` when leaving windowed
+    /// mode.
+    fullscreen_mode: WindowMode,
+    debug_output: Option<debug::OutputGuard>,
 }
 
 impl GrrWindow {
     /// Return the individual components used to construct the window.
     ///
     /// Unnecessary with winit 0.22+ and the use of run_return.
-    pub fn drain(self) -> (WindowedContext<PossiblyCurrent>, EventLoop<()>, Device) {
-        (self.window, self.event_loop, self.device)
+    ///
+    /// The debug output stays installed until the returned guard is
+    /// dropped.
+    pub fn drain(
+        self,
+    ) -> (
+        WindowedContext<PossiblyCurrent>,
+        EventLoop<()>,
+        Device,
+        Option<debug::OutputGuard>,
+    ) {
+        (self.window, self.event_loop, self.device, self.debug_output)
+    }
+
+    /// Take the first OpenGL error reported to the debug output since
+    /// the last call.
+    pub fn take_gl_error(&self) -> Option<debug::DebugError> {
+        self.debug_output.as_ref().and_then(|o| o.take_error())
+    }
+
+    /// The default framebuffer.
+    pub fn framebuffer(&self) -> grr::Framebuffer {
+        grr::Framebuffer::DEFAULT
+    }
+
+    /// Monitors the window can be placed on, in the order used by
+    /// `WindowMode`.
+    pub fn monitors(&self) -> Vec<MonitorHandle> {
+        self.window.window().available_monitors().collect()
+    }
+
+    /// Change how the window is presented.
+    pub fn set_mode(&mut self, mode: &WindowMode) -> Result<(), Error> {
+        let w = self.window.window();
+        let fullscreen = mode.fullscreen(self.monitors(), w.current_monitor())?;
+        w.set_fullscreen(fullscreen);
+        if *mode != WindowMode::Windowed {
+            self.fullscreen_mode = mode.clone();
+        }
+        Ok(())
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.window.window().fullscreen().is_some()
+    }
+
+    /// Switch between windowed mode and the last fullscreen mode
+    /// used, which defaults to borderless on the current monitor.
+    pub fn toggle_fullscreen(&mut self) -> Result<(), Error> {
+        if self.is_fullscreen() {
+            self.window.window().set_fullscreen(None);
+            Ok(())
+        } else {
+            let mode = self.fullscreen_mode.clone();
+            self.set_mode(&mode)
+        }
+    }
+
+    pub fn set_maximized(&self, maximized: bool) {
+        self.window.window().set_maximized(maximized);
+    }
+
+    /// Region covering the window's current inner size.
+    pub fn region(&self) -> grr::Region {
+        let size = self.window.window().inner_size();
+        grr::Region {
+            x: 0,
+            y: 0,
+            w: size.width as _,
+            h: size.height as _,
+        }
     }
 }
 
@@ -110,22 +356,260 @@ impl<'d> GrrImgui {
     }
 }
 
+/// Framebuffer standing in for the default framebuffer of a
+/// headless context, with a color and a depth-stencil attachment.
+pub struct OffscreenTarget {
+    framebuffer: grr::Framebuffer,
+    color: (grr::Image, grr::ImageView),
+    depth_stencil: (grr::Image, grr::ImageView),
+    width: u32,
+    height: u32,
+    samples: u32,
+    srgb: bool,
+}
+
+impl OffscreenTarget {
+    pub fn new(
+        device: &Device,
+        width: u32,
+        height: u32,
+        samples: u32,
+        srgb: bool,
+    ) -> Result<OffscreenTarget, Error> {
+        let color_format = if srgb {
+            grr::Format::R8G8B8A8_SRGB
+        } else {
+            grr::Format::R8G8B8A8_UNORM
+        };
+
+        unsafe {
+            let color = create_attachment(device, width, height, samples, color_format)?;
+            let depth_stencil = create_attachment(
+                device,
+                width,
+                height,
+                samples,
+                grr::Format::D24_UNORM_S8_UINT,
+            )
+            .map_err(|e| {
+                delete_attachment(device, color);
+                e
+            })?;
+            let framebuffer = device.create_framebuffer().map_err(|e| {
+                delete_attachment(device, color);
+                delete_attachment(device, depth_stencil);
+                e
+            })?;
+            device.bind_attachments(
+                framebuffer,
+                &[
+                    (
+                        grr::Attachment::Color(0),
+                        grr::AttachmentView::Image(color.1),
+                    ),
+                    (
+                        grr::Attachment::DepthStencil,
+                        grr::AttachmentView::Image(depth_stencil.1),
+                    ),
+                ],
+            );
+
+            Ok(OffscreenTarget {
+                framebuffer,
+                color,
+                depth_stencil,
+                width,
+                height,
+                samples,
+                srgb,
+            })
+        }
+    }
+
+    pub fn framebuffer(&self) -> grr::Framebuffer {
+        self.framebuffer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn color_image(&self) -> grr::Image {
+        self.color.0
+    }
+
+    pub fn depth_stencil_image(&self) -> grr::Image {
+        self.depth_stencil.0
+    }
+
+    /// Region covering the whole target.
+    pub fn region(&self) -> grr::Region {
+        grr::Region {
+            x: 0,
+            y: 0,
+            w: self.width as _,
+            h: self.height as _,
+        }
+    }
+
+    pub fn delete(&self, device: &Device) {
+        unsafe {
+            device.delete_framebuffer(self.framebuffer);
+            delete_attachment(device, self.color);
+            delete_attachment(device, self.depth_stencil);
+        }
+    }
+}
+
+/// Create a single-level 2D image and a view of it, both multisampled
+/// if `samples > 1`.
+unsafe fn create_attachment(
+    device: &Device,
+    width: u32,
+    height: u32,
+    samples: u32,
+    format: grr::Format,
+) -> Result<(grr::Image, grr::ImageView), Error> {
+    let samples = samples.max(1);
+    let image_type = grr::ImageType::D2 {
+        width,
+        height,
+        layers: 1,
+        samples,
+    };
+    let view_type = if samples > 1 {
+        grr::ImageViewType::D2Multisample
+    } else {
+        grr::ImageViewType::D2
+    };
+
+    let image = device.create_image(image_type, format, 1)?;
+    let range = grr::SubresourceRange {
+        levels: 0..1,
+        layers: 0..1,
+    };
+    match device.create_image_view(image, view_type, format, range) {
+        Ok(view) => Ok((image, view)),
+        Err(e) => {
+            device.delete_image(image);
+            Err(e.into())
+        }
+    }
+}
+
+unsafe fn delete_attachment(device: &Device, (image, view): (grr::Image, grr::ImageView)) {
+    device.delete_image_view(view);
+    device.delete_image(image);
+}
+
 /// Headless OpenGL context.
+///
+/// Rendering goes to `target`, which is bound as the draw
+/// framebuffer when the context is built.
 pub struct GrrHeadless {
     pub window: Context<PossiblyCurrent>,
     pub event_loop: EventLoop<()>,
     pub device: Device,
+    pub info: ContextInfo,
+    pub target: OffscreenTarget,
+    debug_output: Option<debug::OutputGuard>,
 }
 
 impl GrrHeadless {
     /// Return the individual components used to construct the window.
     ///
     /// Unnecessary with winit 0.22+ and the use of run_return.
-    pub fn drain(self) -> (Context<PossiblyCurrent>, EventLoop<()>, Device) {
-        (self.window, self.event_loop, self.device)
+    ///
+    /// The caller takes over `target` and must delete it. The debug
+    /// output stays installed until the returned guard is dropped.
+    pub fn drain(
+        self,
+    ) -> (
+        Context<PossiblyCurrent>,
+        EventLoop<()>,
+        Device,
+        OffscreenTarget,
+        Option<debug::OutputGuard>,
+    ) {
+        (
+            self.window,
+            self.event_loop,
+            self.device,
+            self.target,
+            self.debug_output,
+        )
+    }
+
+    /// Take the first OpenGL error reported to the debug output since
+    /// the last call.
+    pub fn take_gl_error(&self) -> Option<debug::DebugError> {
+        self.debug_output.as_ref().and_then(|o| o.take_error())
+    }
+
+    /// The framebuffer standing in for the default framebuffer.
+    pub fn framebuffer(&self) -> grr::Framebuffer {
+        self.target.framebuffer()
+    }
+
+    /// Region covering the whole offscreen framebuffer.
+    pub fn region(&self) -> grr::Region {
+        self.target.region()
+    }
+
+    /// Replace the offscreen framebuffer with one of a new size,
+    /// keeping the sample count, and bind it with a matching viewport
+    /// and scissor.
+    pub fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        let target = OffscreenTarget::new(
+            &self.device,
+            width,
+            height,
+            self.target.samples,
+            self.target.srgb,
+        )?;
+        self.target.delete(&self.device);
+        self.target = target;
+        unsafe {
+            self.device.bind_framebuffer(self.target.framebuffer());
+            set_full_viewport(&self.device, width, height);
+        }
+        Ok(())
     }
 }
 
+/// Set viewport and scissor 0 to cover a `width` by `height`
+/// framebuffer.
+pub(crate) unsafe fn set_full_viewport(device: &Device, width: u32, height: u32) {
+    device.set_viewport(
+        0,
+        &[grr::Viewport {
+            x: 0.0,
+            y: 0.0,
+            w: width as _,
+            h: height as _,
+            n: 0.0,
+            f: 1.0,
+        }],
+    );
+    device.set_scissor(
+        0,
+        &[grr::Region {
+            x: 0,
+            y: 0,
+            w: width as _,
+            h: height as _,
+        }],
+    );
+}
+
 pub struct GrrBuilder {
     resizable: bool,
     samples: Option<u16>,
@@ -133,6 +617,21 @@ pub struct GrrBuilder {
     srgb: bool,
     visible: bool,
     gl_debug: Option<grr::DebugReport>,
+    debug_output: Option<DebugOutput>,
+    gl_version: Option<(u8, u8)>,
+    gl_profile: Option<GlProfile>,
+    forward_compatible: bool,
+    robustness: Robustness,
+    depth_bits: Option<u8>,
+    stencil_bits: Option<u8>,
+    double_buffer: Option<bool>,
+    hardware_acceleration: Option<bool>,
+    headless_size: (u32, u32),
+    mode: WindowMode,
+    maximized: bool,
+    min_size: Option<(f32, f32)>,
+    max_size: Option<(f32, f32)>,
+    icon: Option<PathBuf>,
 }
 
 impl GrrBuilder {
@@ -148,6 +647,21 @@ impl GrrBuilder {
                     | grr::DebugReport::ERROR
                     | grr::DebugReport::PERFORMANCE_WARNING,
             ),
+            debug_output: None,
+            gl_version: None,
+            gl_profile: None,
+            forward_compatible: false,
+            robustness: Robustness::NotRobust,
+            depth_bits: None,
+            stencil_bits: None,
+            double_buffer: None,
+            hardware_acceleration: Some(true),
+            headless_size: (1024, 768),
+            mode: WindowMode::Windowed,
+            maximized: false,
+            min_size: None,
+            max_size: None,
+            icon: None,
         }
     }
 
@@ -180,37 +694,195 @@ impl GrrBuilder {
         }
     }
 
-    fn debug(&self) -> grr::Debug<grr::DebugCallback> {
+    /// Request a specific OpenGL version.
+    pub fn gl_version(self, major: u8, minor: u8) -> GrrBuilder {
+        GrrBuilder {
+            gl_version: Some((major, minor)),
+            ..self
+        }
+    }
+    /// Request a core or compatibility profile.
+    pub fn gl_profile(self, p: impl Into<Option<GlProfile>>) -> GrrBuilder {
+        GrrBuilder {
+            gl_profile: p.into(),
+            ..self
+        }
+    }
+    /// Prefer a forward-compatible context.
+    ///
+    /// glutin has no way to request the flag: it is set on core
+    /// profiles where the platform requires it (macOS) and left to
+    /// the driver elsewhere. So this is only checked after creation,
+    /// logging a warning if the context lacks it, and never fails the
+    /// build. `ContextInfo::forward_compatible` reports the result.
+    pub fn forward_compatible(self, f: bool) -> GrrBuilder {
+        GrrBuilder {
+            forward_compatible: f,
+            ..self
+        }
+    }
+    pub fn robustness(self, r: Robustness) -> GrrBuilder {
+        GrrBuilder {
+            robustness: r,
+            ..self
+        }
+    }
+    pub fn depth_bits(self, bits: impl Into<Option<u8>>) -> GrrBuilder {
+        GrrBuilder {
+            depth_bits: bits.into(),
+            ..self
+        }
+    }
+    pub fn stencil_bits(self, bits: impl Into<Option<u8>>) -> GrrBuilder {
+        GrrBuilder {
+            stencil_bits: bits.into(),
+            ..self
+        }
+    }
+    /// Request double (`Some(true)`) or single buffering, or leave it
+    /// to the platform.
+    pub fn double_buffer(self, d: impl Into<Option<bool>>) -> GrrBuilder {
+        GrrBuilder {
+            double_buffer: d.into(),
+            ..self
+        }
+    }
+    /// Require (`Some(true)`) or forbid hardware acceleration, or
+    /// accept either. Defaults to requiring it.
+    pub fn hardware_acceleration(self, h: impl Into<Option<bool>>) -> GrrBuilder {
+        GrrBuilder {
+            hardware_acceleration: h.into(),
+            ..self
+        }
+    }
+
+    /// Initial presentation of the window.
+    pub fn mode(self, mode: WindowMode) -> GrrBuilder {
+        GrrBuilder { mode, ..self }
+    }
+    pub fn maximized(self, m: bool) -> GrrBuilder {
+        GrrBuilder {
+            maximized: m,
+            ..self
+        }
+    }
+    /// Minimum inner size, in logical pixels.
+    pub fn min_size(self, w: f32, h: f32) -> GrrBuilder {
+        GrrBuilder {
+            min_size: Some((w, h)),
+            ..self
+        }
+    }
+    /// Maximum inner size, in logical pixels.
+    pub fn max_size(self, w: f32, h: f32) -> GrrBuilder {
+        GrrBuilder {
+            max_size: Some((w, h)),
+            ..self
+        }
+    }
+    /// Window icon, loaded from an image file.
+    pub fn icon<P: Into<PathBuf>>(self, path: P) -> GrrBuilder {
+        GrrBuilder {
+            icon: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Size of the offscreen framebuffer of headless builds, which
+    /// also use the `multisamples` sample count.
+    pub fn headless_size(self, width: u32, height: u32) -> GrrBuilder {
+        GrrBuilder {
+            headless_size: (width, height),
+            ..self
+        }
+    }
+
+    /// Context options shared by windowed and headless builds.
+    fn context_builder(&self) -> glutin::ContextBuilder<'static, glutin::NotCurrent> {
+        let mut cx = glutin::ContextBuilder::new()
+            .with_vsync(self.vsync)
+            .with_srgb(self.srgb)
+            .with_gl_debug_flag(self.gl_debug.is_some())
+            .with_gl_robustness(self.robustness)
+            .with_double_buffer(self.double_buffer)
+            .with_hardware_acceleration(self.hardware_acceleration);
+
+        if let Some(version) = self.gl_version {
+            cx = cx.with_gl(GlRequest::Specific(glutin::Api::OpenGl, version));
+        }
+        if let Some(profile) = self.gl_profile {
+            cx = cx.with_gl_profile(profile);
+        }
+        if let Some(bits) = self.depth_bits {
+            cx = cx.with_depth_buffer(bits);
+        }
+        if let Some(bits) = self.stencil_bits {
+            cx = cx.with_stencil_buffer(bits);
+        }
+        cx
+    }
+
+    /// Warn about preferences that could not be requested directly
+    /// and were not met by the created context.
+    fn check_context(&self, info: &ContextInfo) {
+        if self.forward_compatible && !info.forward_compatible {
+            log::warn!(
+                "requested a forward-compatible context, but the driver created one without it"
+            );
+        }
+    }
+
+    /// Route debug messages to `output` instead of the `log` crate.
+    ///
+    /// The output is installed on the building thread for as long as
+    /// the window or headless context lives; see `debug::install`.
+    pub fn debug_output(self, output: DebugOutput) -> GrrBuilder {
+        GrrBuilder {
+            debug_output: Some(output),
+            ..self
+        }
+    }
+
+    /// Install the debug output and return the `grr` debug setting.
+    fn debug(&mut self) -> (grr::Debug<grr::DebugCallback>, Option<debug::OutputGuard>) {
         if let Some(flags) = self.gl_debug {
-            grr::Debug::Enable {
-                callback: |report, source, dtype, id, msg| {
-                    println!(
-                        "{:8} {:?} ({:?}/{:?}): {:?}",
-                        id, report, source, dtype, msg
-                    );
-                },
+            let guard = debug::install(self.debug_output.take().unwrap_or_default());
+            let debug = grr::Debug::Enable {
+                callback: debug::route_message,
                 flags,
-            }
+            };
+            (debug, Some(guard))
         } else {
-            grr::Debug::Disable
+            (grr::Debug::Disable, None)
         }
     }
 
-    pub fn build_windowed(self, title: &str, w: f32, h: f32) -> Result<GrrWindow, Error> {
+    pub fn build_windowed(mut self, title: &str, w: f32, h: f32) -> Result<GrrWindow, Error> {
         let event_loop = EventLoop::new();
-        let wb = WindowBuilder::new()
+        let fullscreen = self
+            .mode
+            .fullscreen(event_loop.available_monitors().collect(), event_loop.primary_monitor())?;
+        let mut wb = WindowBuilder::new()
             .with_title(title)
             .with_resizable(self.resizable)
             .with_visible(self.visible)
+            .with_maximized(self.maximized)
+            .with_fullscreen(fullscreen)
             .with_inner_size(LogicalSize {
                 width: w,
                 height: h,
             });
+        if let Some((width, height)) = self.min_size {
+            wb = wb.with_min_inner_size(LogicalSize { width, height });
+        }
+        if let Some((width, height)) = self.max_size {
+            wb = wb.with_max_inner_size(LogicalSize { width, height });
+        }
+        if let Some(path) = &self.icon {
+            wb = wb.with_window_icon(Some(load_icon(path)?));
+        }
         let window = unsafe {
-            let mut cx = glutin::ContextBuilder::new()
-                .with_vsync(self.vsync)
-                .with_srgb(self.srgb)
-                .with_gl_debug_flag(self.gl_debug.is_some());
+            let mut cx = self.context_builder();
 
             if let Some(ms) = self.samples {
                 if ms > 0 {
@@ -223,43 +895,71 @@ impl GrrBuilder {
                 .map_err(|x| x.1)?
         };
 
+        let (gl_debug, debug_output) = self.debug();
         let device = unsafe {
             Device::new(
                 |symbol| window.get_proc_address(symbol) as *const _,
-                self.debug(),
+                gl_debug,
             )
         };
+        raw_gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+        let info = ContextInfo::query(Some(window.get_pixel_format()))?;
+        self.check_context(&info);
+
+        let fullscreen_mode = match self.mode {
+            WindowMode::Windowed => WindowMode::Borderless { monitor: None },
+            mode => mode,
+        };
 
         Ok(GrrWindow {
             window,
             event_loop,
             device,
+            info,
+            fullscreen_mode,
+            debug_output,
         })
     }
 
-    pub fn build_headless(self) -> Result<GrrHeadless, Error> {
+    pub fn build_headless(mut self) -> Result<GrrHeadless, Error> {
         let event_loop = EventLoop::new();
         let window = unsafe {
-            glutin::ContextBuilder::new()
-                .with_vsync(self.vsync)
-                .with_srgb(self.srgb)
-                .with_gl_debug_flag(self.gl_debug.is_some())
+            self.context_builder()
                 .build_surfaceless(&event_loop)?
                 .make_current()
                 .map_err(|x| x.1)?
         };
 
+        let (gl_debug, debug_output) = self.debug();
         let device = unsafe {
             Device::new(
                 |symbol| window.get_proc_address(symbol) as *const _,
-                self.debug(),
+                gl_debug,
             )
         };
+        raw_gl::load_with(|symbol| window.get_proc_address(symbol) as *const _);
+
+        let info = ContextInfo::query(None)?;
+        self.check_context(&info);
+
+        // Surfaceless contexts have no default framebuffer, so provide
+        // an offscreen one.
+        let (width, height) = self.headless_size;
+        let samples = self.samples.unwrap_or(0).max(1) as u32;
+        let target = OffscreenTarget::new(&device, width, height, samples, self.srgb)?;
+        unsafe {
+            device.bind_framebuffer(target.framebuffer());
+            set_full_viewport(&device, width, height);
+        }
 
         Ok(GrrHeadless {
             window,
             event_loop,
             device,
+            info,
+            target,
+            debug_output,
         })
     }
 }
@@ -0,0 +1,73 @@
+use grr_util::atlas::{AtlasError, PackedRect, RectPacker};
+
+fn overlaps(a: &PackedRect, b: &PackedRect) -> bool {
+    a.page == b.page
+        && a.x < b.x + b.width
+        && b.x < a.x + a.width
+        && a.y < b.y + b.height
+        && b.y < a.y + a.height
+}
+
+#[test]
+fn test_pack_no_overlap() {
+    let sizes: Vec<(u32, u32)> = (1..40).map(|i| (i % 7 + 3, i % 5 + 2)).collect();
+    let packer = RectPacker::new(32, 32);
+    let placed = packer.pack(&sizes).unwrap();
+
+    assert_eq!(placed.len(), sizes.len());
+    for (p, s) in placed.iter().zip(sizes.iter()) {
+        assert_eq!((p.width, p.height), *s);
+        assert!(p.x + p.width <= 32 && p.y + p.height <= 32);
+    }
+    for (i, a) in placed.iter().enumerate() {
+        for b in &placed[i + 1..] {
+            assert!(!overlaps(a, b), "{:?} overlaps {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn test_pack_padding() {
+    let packer = RectPacker {
+        padding: 1,
+        ..RectPacker::new(8, 8)
+    };
+    let placed = packer.pack(&[(2, 2), (2, 2)]).unwrap();
+
+    assert_eq!((placed[0].x, placed[0].y), (1, 1));
+    assert_eq!((placed[1].x, placed[1].y), (5, 1));
+}
+
+#[test]
+fn test_pack_multiple_pages() {
+    let packer = RectPacker::new(4, 4);
+    let placed = packer.pack(&[(4, 4), (4, 4), (2, 2)]).unwrap();
+
+    let mut pages: Vec<_> = placed.iter().map(|p| p.page).collect();
+    pages.sort_unstable();
+    assert_eq!(pages, vec![0, 1, 2]);
+}
+
+#[test]
+fn test_pack_errors() {
+    let packer = RectPacker::new(4, 4);
+    assert!(matches!(
+        packer.pack(&[(1, 1), (5, 1)]),
+        Err(AtlasError::TooLarge(1))
+    ));
+
+    let limited = RectPacker {
+        max_pages: Some(1),
+        ..packer
+    };
+    assert!(matches!(
+        limited.pack(&[(4, 4), (1, 1)]),
+        Err(AtlasError::TooManyPages(1))
+    ));
+    // Padding that overflows is too large rather than wrapping.
+    let padded = RectPacker {
+        padding: u32::MAX / 2 + 1,
+        ..RectPacker::new(u32::MAX, u32::MAX)
+    };
+    assert!(matches!(padded.pack(&[(1, 1)]), Err(AtlasError::TooLarge(0))));
+}
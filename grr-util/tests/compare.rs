@@ -0,0 +1,111 @@
+use grr_util::compare::*;
+use nalgebra::Vector4;
+use ndarray::Array2;
+
+#[test]
+fn test_identical() {
+    let a = Array2::from_elem((4, 4), Vector4::new(0.2, 0.4, 0.6, 1.0));
+    let diff = compare_arrays(&a, &a, &Tolerance::default()).unwrap();
+
+    assert!(diff.passed());
+    assert_eq!(diff.rmse, 0.0);
+    assert!(diff.psnr.is_infinite());
+    assert_eq!(diff.max_abs_error, Vector4::zeros());
+}
+
+#[test]
+fn test_metrics() {
+    let a = Array2::from_elem((2, 2), Vector4::new(0.0, 0.0, 0.0, 1.0));
+    let mut b = a.clone();
+    b[(0, 1)].x = 0.5;
+    b[(1, 1)].y = 0.001;
+
+    let diff = compare_arrays(&a, &b, &Tolerance::uniform(0.01)).unwrap();
+    assert_eq!(diff.num_differing, 1);
+    assert_eq!(diff.max_abs_error, Vector4::new(0.5, 0.001, 0.0, 0.0));
+    assert_eq!(diff.error_map[(0, 1)], 0.5);
+
+    // 16 channel values, errors of 0.5 and 0.001.
+    let mse = (0.25f32 + 0.000_001) / 16.0;
+    assert!((diff.rmse - mse.sqrt()).abs() < 1e-6);
+    assert!((diff.psnr + 10.0 * mse.log10()).abs() < 1e-3);
+}
+
+#[test]
+fn test_per_channel_tolerance() {
+    let a = Array2::from_elem((1, 1), Vector4::new(0.0, 0.0, 0.0, 0.0));
+    let b = Array2::from_elem((1, 1), Vector4::new(0.0, 0.0, 0.0, 1.0));
+
+    assert!(!compare_arrays(&a, &b, &Tolerance::uniform(0.1)).unwrap().passed());
+    assert!(compare_arrays(&a, &b, &Tolerance::rgb(0.1)).unwrap().passed());
+}
+
+#[test]
+fn test_non_finite() {
+    let a = Array2::from_elem((1, 2), Vector4::new(0.0, 0.0, 0.0, 1.0));
+    let mut b = a.clone();
+    b[(0, 0)].x = std::f32::NAN;
+    b[(0, 1)].y = std::f32::INFINITY;
+
+    let diff = compare_arrays(&a, &b, &Tolerance::uniform(1.0)).unwrap();
+    assert_eq!(diff.num_differing, 2);
+    assert!(diff.max_abs_error.x.is_infinite());
+    assert!(diff.max_abs_error.y.is_infinite());
+    assert!(!diff.passed());
+}
+
+#[test]
+fn test_size_mismatch() {
+    let a = Array2::from_elem((2, 2), Vector4::zeros());
+    let b = Array2::from_elem((2, 3), Vector4::zeros());
+
+    assert!(matches!(
+        compare_arrays(&a, &b, &Tolerance::default()),
+        Err(CompareError::SizeMismatch((2, 2), (2, 3)))
+    ));
+}
+
+#[test]
+fn test_heat_map() {
+    let a = Array2::from_elem((1, 2), Vector4::zeros());
+    let mut b = a.clone();
+    b[(0, 1)].z = 0.25;
+
+    let map = compare_arrays(&a, &b, &Tolerance::default())
+        .unwrap()
+        .heat_map(None);
+    assert_eq!(map[(0, 0)], Vector4::new(0, 0, 0, 255));
+    assert_eq!(map[(0, 1)], Vector4::new(255, 255, 255, 255));
+}
+
+/// Return a directory for the files of one test, unique to the test
+/// and the process, so that concurrent runs do not collide.
+fn test_dir(test: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "grr_util_compare_{}_{}",
+        std::process::id(),
+        test
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_compare_files() {
+    let dir = test_dir("compare_files");
+    let pa = dir.join("a.png");
+    let pb = dir.join("b.png");
+    image::save_buffer(&pa, &[0, 0, 0, 255, 10, 20, 30, 255], 2, 1, image::ColorType::Rgba8)
+        .unwrap();
+    image::save_buffer(&pb, &[0, 0, 0, 255, 11, 20, 30, 255], 2, 1, image::ColorType::Rgba8)
+        .unwrap();
+
+    let diff = compare_files(&pa, &pb, &Tolerance::uniform(1.5 / 255.0)).unwrap();
+    assert!(diff.passed());
+    assert!((diff.max_abs_error.x - 1.0 / 255.0).abs() < 1e-6);
+
+    let strict = compare_files(&pa, &pb, &Tolerance::uniform(0.0)).unwrap();
+    assert_eq!(strict.num_differing, 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,140 @@
+use grr_util::debug::{self, DebugMessage, DebugOutput};
+use std::sync::{Arc, Mutex};
+
+fn message(report: grr::DebugReport, id: u32, text: &str) -> DebugMessage {
+    DebugMessage {
+        report,
+        source: grr::DebugSource::Api,
+        ty: grr::DebugType::Other,
+        id,
+        message: text,
+    }
+}
+
+fn capture() -> (Arc<Mutex<Vec<u32>>>, DebugOutput) {
+    let ids = Arc::new(Mutex::new(Vec::new()));
+    let sink_ids = ids.clone();
+    let output = DebugOutput::sink(move |m| sink_ids.lock().unwrap().push(m.id));
+    (ids, output)
+}
+
+#[test]
+fn test_severity() {
+    assert_eq!(
+        message(grr::DebugReport::ERROR, 0, "").level(),
+        log::Level::Error
+    );
+    assert_eq!(
+        message(grr::DebugReport::PERFORMANCE_WARNING, 0, "").level(),
+        log::Level::Warn
+    );
+}
+
+#[test]
+fn test_filter_and_dedup() {
+    let (ids, output) = capture();
+    let mut output = output
+        .ignore_id(7)
+        .ignore_type(grr::DebugType::Performance)
+        .dedup(true);
+
+    output.handle(&message(grr::DebugReport::WARNING, 7, "ignored"));
+    output.handle(&message(grr::DebugReport::WARNING, 1, "a"));
+    output.handle(&message(grr::DebugReport::WARNING, 1, "a"));
+    output.handle(&message(grr::DebugReport::WARNING, 1, "b"));
+    output.handle(&DebugMessage {
+        ty: grr::DebugType::Performance,
+        ..message(grr::DebugReport::WARNING, 2, "slow")
+    });
+
+    assert_eq!(*ids.lock().unwrap(), vec![1, 1]);
+}
+
+#[test]
+fn test_dedup_capacity() {
+    let (ids, output) = capture();
+    let mut output = output.dedup(true);
+
+    for i in 0..=debug::DEDUP_CAPACITY {
+        output.handle(&message(grr::DebugReport::WARNING, i as u32, "a"));
+    }
+    // The record was cleared when full, so the first message repeats.
+    output.handle(&message(grr::DebugReport::WARNING, 0, "a"));
+
+    let ids = ids.lock().unwrap();
+    assert_eq!(ids.len(), debug::DEDUP_CAPACITY + 2);
+    assert_eq!(ids.last(), Some(&0));
+}
+
+#[test]
+fn test_first_error() {
+    let (_, mut output) = capture();
+
+    output.handle(&message(grr::DebugReport::WARNING, 1, "warn"));
+    assert!(output.take_error().is_none());
+
+    output.handle(&message(grr::DebugReport::ERROR, 2, "first"));
+    output.handle(&message(grr::DebugReport::ERROR, 3, "second"));
+    let error = output.take_error().unwrap();
+    assert_eq!((error.id, error.message.as_str()), (2, "first"));
+    assert!(output.take_error().is_none());
+}
+
+#[test]
+fn test_installed_outputs() {
+    let route = |id| {
+        debug::route_message(
+            grr::DebugReport::ERROR,
+            grr::DebugSource::Api,
+            grr::DebugType::Error,
+            id,
+            "error",
+        )
+    };
+
+    let (first_ids, first) = capture();
+    let first = debug::install(first);
+    let (second_ids, second) = capture();
+    let second = debug::install(second);
+
+    route(1);
+    drop(second);
+    route(2);
+
+    assert_eq!(*first_ids.lock().unwrap(), vec![2]);
+    assert_eq!(*second_ids.lock().unwrap(), vec![1]);
+    assert_eq!(first.take_error().map(|e| e.id), Some(2));
+    assert!(debug::take_error().is_none());
+}
+
+#[test]
+fn test_check_errors_without_panic_mode() {
+    let (_, output) = capture();
+    let _guard = debug::install(output);
+    debug::route_message(
+        grr::DebugReport::ERROR,
+        grr::DebugSource::Api,
+        grr::DebugType::Error,
+        1,
+        "error",
+    );
+
+    debug::check_errors();
+    assert_eq!(debug::take_error().map(|e| e.id), Some(1));
+}
+
+#[test]
+#[should_panic(expected = "OpenGL error 1: error")]
+fn test_panic_on_error() {
+    let (_, output) = capture();
+    let _guard = debug::install(output.panic_on_error(true));
+    debug::route_message(
+        grr::DebugReport::ERROR,
+        grr::DebugSource::Api,
+        grr::DebugType::Error,
+        1,
+        "error",
+    );
+
+    debug::check_errors();
+}
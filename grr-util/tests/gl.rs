@@ -0,0 +1,168 @@
+//! Tests that need an OpenGL context.
+//!
+//! glutin requires the event loop to be created on the main thread,
+//! so these run without the default test harness, in sequence, on a
+//! single headless context. They are skipped where no context can be
+//! created.
+use grr_util::image_manager::{Error, ImageLoadOptions, ImageViewDesc};
+use grr_util::recorder::{self, FrameRecorder, RecorderOptions};
+use grr_util::window::OffscreenTarget;
+use grr_util::{GrrBuilder, GrrHeadless, ImageManager};
+use std::path::PathBuf;
+
+type Test = fn(&GrrHeadless);
+
+const TESTS: &[(&str, Test)] = &[
+    (
+        "reload_failure_leaves_image_unchanged",
+        reload_failure_leaves_image_unchanged,
+    ),
+    ("recorder_drop_writes_frames", recorder_drop_writes_frames),
+    ("recorder_drop_closes_ffmpeg", recorder_drop_closes_ffmpeg),
+    (
+        "multisample_offscreen_target_resolves",
+        multisample_offscreen_target_resolves,
+    ),
+];
+
+/// Return a fresh path in the temp directory, unique to this process.
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("grr-util-gl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+fn reload_failure_leaves_image_unchanged(headless: &GrrHeadless) {
+    let device = &headless.device;
+    let path = temp_path("reload.png");
+    image::RgbaImage::from_pixel(8, 8, image::Rgba([255, 0, 0, 255]))
+        .save(&path)
+        .unwrap();
+
+    let mut images = ImageManager::new(device);
+    let image = images
+        .load_image(&path, ImageLoadOptions::default())
+        .unwrap();
+    let view = images
+        .create_image_view(image, &ImageViewDesc::new().levels(2..4))
+        .unwrap();
+    let view_handle = grr::Object::handle(&images.get_image_view_handle(view).unwrap());
+
+    // Two levels are too few for the view.
+    image::RgbaImage::from_pixel(2, 2, image::Rgba([0, 255, 0, 255]))
+        .save(&path)
+        .unwrap();
+    assert!(matches!(
+        images.reload_image(image),
+        Err(Error::InvalidLevelRange(_))
+    ));
+
+    let info = images.image_info(image).unwrap();
+    assert_eq!(info.extent.width, 8);
+    assert_eq!(info.extent.height, 8);
+    assert_eq!(info.num_mipmap_levels, 4);
+    assert_eq!(
+        grr::Object::handle(&images.get_image_view_handle(view).unwrap()),
+        view_handle
+    );
+    let data: Vec<u8> = images.get_texture_vec(image).unwrap();
+    assert_eq!(&data[..4], &[255, 0, 0, 255]);
+
+    images.clear();
+}
+
+fn recorder_drop_writes_frames(headless: &GrrHeadless) {
+    let dir = temp_path("recorder_png");
+    let options = RecorderOptions {
+        every_nth: 2,
+        ..RecorderOptions::png_sequence(&dir)
+    };
+
+    let mut rec = FrameRecorder::new(&headless.device, options).unwrap();
+    for _ in 0..5 {
+        rec.record(headless.framebuffer(), headless.region()).unwrap();
+    }
+    assert_eq!(rec.num_recorded(), 3);
+    // Dropped without `finish`.
+    drop(rec);
+
+    for i in 0..3 {
+        let path = dir.join(recorder::frame_file_name("frame_#####.png", i));
+        assert!(path.exists(), "{} was not written", path.display());
+    }
+}
+
+fn recorder_drop_closes_ffmpeg(headless: &GrrHeadless) {
+    if !recorder::ffmpeg_available() {
+        eprintln!("skipping recorder_drop_closes_ffmpeg: ffmpeg not found");
+        return;
+    }
+    let path = temp_path("recorder.mp4");
+    let region = grr::Region {
+        x: 0,
+        y: 0,
+        w: 64,
+        h: 64,
+    };
+
+    let mut rec = FrameRecorder::new(&headless.device, RecorderOptions::ffmpeg(&path, 30)).unwrap();
+    for _ in 0..3 {
+        rec.record(headless.framebuffer(), region).unwrap();
+    }
+    // Dropping waits for ffmpeg, so the video is complete.
+    drop(rec);
+
+    assert!(std::fs::metadata(&path).unwrap().len() > 0);
+}
+
+fn multisample_offscreen_target_resolves(headless: &GrrHeadless) {
+    let device = &headless.device;
+    let multisampled = OffscreenTarget::new(device, 16, 16, 4, false).unwrap();
+    let resolved = OffscreenTarget::new(device, 16, 16, 1, false).unwrap();
+    headless.take_gl_error();
+
+    // Blitting fails with an error if either framebuffer is incomplete.
+    unsafe {
+        device.blit(
+            multisampled.framebuffer(),
+            multisampled.region(),
+            resolved.framebuffer(),
+            resolved.region(),
+            grr::Filter::Nearest,
+        );
+    }
+    let error = headless.take_gl_error();
+
+    multisampled.delete(device);
+    resolved.delete(device);
+    assert!(error.is_none(), "{}", error.unwrap());
+}
+
+/// Create the shared context, or return `None` if there is no
+/// OpenGL driver or display to create it with.
+fn headless() -> Option<GrrHeadless> {
+    let res = std::panic::catch_unwind(|| GrrBuilder::new().build_headless());
+    match res {
+        Ok(Ok(headless)) => Some(headless),
+        Ok(Err(e)) => {
+            eprintln!("skipping OpenGL tests: {}", e);
+            None
+        }
+        Err(_) => {
+            eprintln!("skipping OpenGL tests: no event loop available");
+            None
+        }
+    }
+}
+
+fn main() {
+    let headless = match headless() {
+        Some(h) => h,
+        None => return,
+    };
+
+    for (name, test) in TESTS {
+        test(&headless);
+        println!("test {} ... ok", name);
+    }
+}
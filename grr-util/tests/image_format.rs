@@ -0,0 +1,47 @@
+use grr::{ImageType, ImageViewType};
+use grr_util::image_format::{image_memory_size, view_type_compatible};
+
+fn d2(layers: u32, samples: u32) -> ImageType {
+    ImageType::D2 {
+        width: 16,
+        height: 16,
+        layers,
+        samples,
+    }
+}
+
+#[test]
+fn test_multisample_view_types() {
+    assert!(view_type_compatible(d2(1, 4), ImageViewType::D2Multisample, 1));
+    assert!(view_type_compatible(d2(2, 4), ImageViewType::D2MultisampleArray, 2));
+    assert!(!view_type_compatible(d2(1, 4), ImageViewType::D2, 1));
+    assert!(!view_type_compatible(d2(2, 4), ImageViewType::D2Array, 2));
+}
+
+#[test]
+fn test_single_sample_view_types() {
+    assert!(view_type_compatible(d2(1, 1), ImageViewType::D2, 1));
+    assert!(view_type_compatible(d2(6, 1), ImageViewType::Cube, 6));
+    assert!(!view_type_compatible(d2(1, 1), ImageViewType::D2Multisample, 1));
+    assert!(!view_type_compatible(d2(2, 1), ImageViewType::D2MultisampleArray, 2));
+}
+
+#[test]
+fn test_memory_size() {
+    assert_eq!(
+        image_memory_size(d2(2, 1), grr::Format::R8G8B8A8_UNORM, 1),
+        Some(16 * 16 * 4 * 2)
+    );
+
+    // Too large to fit in 64 bits.
+    let huge = ImageType::D2 {
+        width: 1 << 20,
+        height: 1 << 20,
+        layers: 1 << 20,
+        samples: 1,
+    };
+    assert_eq!(
+        image_memory_size(huge, grr::Format::R32G32B32A32_SFLOAT, 1),
+        None
+    );
+}
@@ -0,0 +1,151 @@
+use glutin::dpi::PhysicalPosition;
+use glutin::event::{
+    DeviceEvent, DeviceId, ElementState, Event, KeyboardInput, ModifiersState, MouseButton,
+    MouseScrollDelta, TouchPhase, VirtualKeyCode, WindowEvent,
+};
+use glutin::window::WindowId;
+use grr_util::input::InputState;
+
+fn window_event(event: WindowEvent<'static>) -> Event<'static, ()> {
+    Event::WindowEvent {
+        window_id: unsafe { WindowId::dummy() },
+        event,
+    }
+}
+
+#[allow(deprecated)]
+fn key(key: VirtualKeyCode, state: ElementState) -> Event<'static, ()> {
+    window_event(WindowEvent::KeyboardInput {
+        device_id: unsafe { DeviceId::dummy() },
+        input: KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: ModifiersState::empty(),
+        },
+        is_synthetic: false,
+    })
+}
+
+#[allow(deprecated)]
+fn button(button: MouseButton, state: ElementState) -> Event<'static, ()> {
+    window_event(WindowEvent::MouseInput {
+        device_id: unsafe { DeviceId::dummy() },
+        state,
+        button,
+        modifiers: ModifiersState::empty(),
+    })
+}
+
+#[allow(deprecated)]
+fn cursor(x: f64, y: f64) -> Event<'static, ()> {
+    window_event(WindowEvent::CursorMoved {
+        device_id: unsafe { DeviceId::dummy() },
+        position: PhysicalPosition::new(x, y),
+        modifiers: ModifiersState::empty(),
+    })
+}
+
+#[test]
+fn test_key_transitions() {
+    let mut input = InputState::new();
+    input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+    assert!(input.key_pressed(VirtualKeyCode::W));
+    assert!(input.key_down(VirtualKeyCode::W));
+
+    // Repeats while held are not new presses.
+    input.begin_frame();
+    input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+    assert!(!input.key_pressed(VirtualKeyCode::W));
+    assert!(input.key_down(VirtualKeyCode::W));
+
+    input.begin_frame();
+    input.handle_event(&key(VirtualKeyCode::W, ElementState::Released));
+    assert!(input.key_released(VirtualKeyCode::W));
+    assert!(!input.key_down(VirtualKeyCode::W));
+}
+
+#[test]
+fn test_cursor_and_scroll() {
+    let mut input = InputState::new();
+    input.handle_event(&cursor(10.0, 10.0));
+    input.begin_frame();
+    input.handle_event(&cursor(15.0, 8.0));
+    input.handle_event(&cursor(20.0, 6.0));
+    #[allow(deprecated)]
+    input.handle_event(&window_event(WindowEvent::MouseWheel {
+        device_id: unsafe { DeviceId::dummy() },
+        delta: MouseScrollDelta::LineDelta(0.0, 2.0),
+        phase: TouchPhase::Moved,
+        modifiers: ModifiersState::empty(),
+    }));
+
+    assert_eq!(input.cursor_position(), Some((20.0, 6.0)));
+    assert_eq!(input.cursor_delta(), (10.0, -4.0));
+    assert_eq!(input.scroll(), (0.0, 2.0));
+
+    input.begin_frame();
+    assert_eq!(input.cursor_delta(), (0.0, 0.0));
+    assert_eq!(input.scroll(), (0.0, 0.0));
+}
+
+#[test]
+fn test_ui_capture() {
+    let mut input = InputState::new();
+    input.set_ui_capture(true, false);
+    input.handle_event(&button(MouseButton::Left, ElementState::Pressed));
+    input.handle_event(&key(VirtualKeyCode::A, ElementState::Pressed));
+    assert!(!input.button_pressed(MouseButton::Left));
+    assert!(input.key_pressed(VirtualKeyCode::A));
+
+    // Buttons and keys held before the UI took them are still
+    // released, and reported while captured.
+    input.set_ui_capture(false, false);
+    input.handle_event(&button(MouseButton::Right, ElementState::Pressed));
+    input.set_ui_capture(true, true);
+    input.begin_frame();
+    input.handle_event(&button(MouseButton::Right, ElementState::Released));
+    input.handle_event(&key(VirtualKeyCode::A, ElementState::Released));
+    assert!(input.button_released(MouseButton::Right));
+    assert!(input.key_released(VirtualKeyCode::A));
+
+    input.set_ui_capture(false, false);
+    assert!(!input.button_down(MouseButton::Right));
+    assert!(!input.key_down(VirtualKeyCode::A));
+}
+
+#[test]
+fn test_focus_loss() {
+    let mut input = InputState::new();
+    input.handle_event(&key(VirtualKeyCode::W, ElementState::Pressed));
+    input.handle_event(&button(MouseButton::Left, ElementState::Pressed));
+    input.handle_event(&window_event(WindowEvent::ModifiersChanged(ModifiersState::SHIFT)));
+
+    input.begin_frame();
+    input.handle_event(&window_event(WindowEvent::Focused(false)));
+    assert!(input.key_released(VirtualKeyCode::W));
+    assert!(!input.key_down(VirtualKeyCode::W));
+    assert!(input.button_released(MouseButton::Left));
+    assert!(!input.button_down(MouseButton::Left));
+    assert_eq!(input.modifiers(), ModifiersState::empty());
+}
+
+#[test]
+fn test_mouse_motion() {
+    let motion = |x, y| Event::<()>::DeviceEvent {
+        device_id: unsafe { DeviceId::dummy() },
+        event: DeviceEvent::MouseMotion { delta: (x, y) },
+    };
+
+    let mut input = InputState::new();
+    input.handle_event(&motion(3.0, -1.0));
+    input.handle_event(&motion(2.0, 4.0));
+    assert_eq!(input.mouse_motion(), (5.0, 3.0));
+
+    input.set_ui_capture(true, false);
+    assert_eq!(input.mouse_motion(), (0.0, 0.0));
+
+    input.set_ui_capture(false, false);
+    input.begin_frame();
+    assert_eq!(input.mouse_motion(), (0.0, 0.0));
+}
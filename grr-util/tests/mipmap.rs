@@ -0,0 +1,137 @@
+use grr_util::mipmap::*;
+use nalgebra::Vector4;
+use ndarray::Array2;
+
+fn approx(a: f32, b: f32, eps: f32) -> bool {
+    (a - b).abs() <= eps
+}
+
+#[test]
+fn test_chain_sizes() {
+    let level0 = Array2::from_elem((4, 8), Vector4::new(0.25, 0.5, 0.75, 1.0));
+    let chain = generate_mip_chain(&level0, &MipOptions::default()).unwrap();
+
+    let shapes: Vec<_> = chain.iter().map(|l| (l.nrows(), l.ncols())).collect();
+    assert_eq!(shapes, vec![(4, 8), (2, 4), (1, 2), (1, 1)]);
+}
+
+#[test]
+fn test_constant_preserved_by_every_filter() {
+    let c = Vector4::new(0.25, 0.5, 0.75, 1.0);
+    let level0 = Array2::from_elem((16, 16), c);
+    for filter in &[
+        MipFilter::Box,
+        MipFilter::Lanczos(3),
+        MipFilter::Kaiser {
+            alpha: 4.0,
+            width: 3.0,
+        },
+    ] {
+        let options = MipOptions {
+            filter: *filter,
+            ..MipOptions::default()
+        };
+        for level in generate_mip_chain(&level0, &options).unwrap() {
+            assert!(level.iter().all(|v| (v - c).abs().max() < 1e-4));
+        }
+    }
+}
+
+#[test]
+fn test_srgb_is_gamma_correct() {
+    let black = Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let white = Vector4::new(1.0, 1.0, 1.0, 1.0);
+    let level0 = Array2::from_shape_fn((2, 2), |(y, x)| if (x + y) % 2 == 0 { black } else { white });
+
+    let linear = generate_mip_chain(&level0, &MipOptions::default()).unwrap();
+    assert!(approx(linear[1][(0, 0)].x, 0.5, 1e-5));
+
+    let srgb = generate_mip_chain(
+        &level0,
+        &MipOptions {
+            srgb: true,
+            ..MipOptions::default()
+        },
+    )
+    .unwrap();
+    // sRGB encoding of linear 0.5
+    assert!(approx(srgb[1][(0, 0)].x, 0.7354, 1e-3));
+    assert!(approx(srgb[1][(0, 0)].w, 1.0, 1e-6));
+}
+
+#[test]
+fn test_normals_renormalized() {
+    let encode = |x: f32, y: f32, z: f32| Vector4::new(x * 0.5 + 0.5, y * 0.5 + 0.5, z * 0.5 + 0.5, 1.0);
+    let s = 0.5f32.sqrt();
+    let level0 = Array2::from_shape_fn((2, 2), |(_, x)| {
+        if x == 0 {
+            encode(s, 0.0, s)
+        } else {
+            encode(-s, 0.0, s)
+        }
+    });
+
+    let chain = generate_mip_chain(
+        &level0,
+        &MipOptions {
+            normal_map: true,
+            ..MipOptions::default()
+        },
+    )
+    .unwrap();
+    let v = chain[1][(0, 0)];
+    let n = v.xyz().map(|c| c * 2.0 - 1.0);
+    assert!(approx(n.norm(), 1.0, 1e-4));
+    assert!(approx(n.z, 1.0, 1e-4));
+}
+
+#[test]
+fn test_alpha_coverage_preserved() {
+    // Noisy alpha-tested texels fade out with plain filtering.
+    let level0 = Array2::from_shape_fn((32, 32), |(y, x)| {
+        let h = ((x as u64 * 2_654_435_761) ^ (y as u64 * 40503)) % 1000;
+        Vector4::new(1.0, 1.0, 1.0, h as f32 / 999.0)
+    });
+    let coverage = |l: &Array2<Vector4<f32>>| {
+        l.iter().filter(|v| v.w > 0.7).count() as f32 / l.len() as f32
+    };
+    let target = coverage(&level0);
+
+    let plain = generate_mip_chain(&level0, &MipOptions::default()).unwrap();
+    assert!(coverage(&plain[1]) < target - 0.1);
+
+    let options = MipOptions {
+        alpha_cutoff: Some(0.7),
+        ..MipOptions::default()
+    };
+    let chain = generate_mip_chain(&level0, &options).unwrap();
+    assert!(approx(coverage(&chain[1]), target, 0.05));
+    assert!(approx(coverage(&chain[2]), target, 0.05));
+}
+
+#[test]
+fn test_rgba8_chain() {
+    let level0 = Array2::from_elem((4, 4), Vector4::new(10u8, 20, 30, 255));
+    let chain = generate_mip_chain_rgba8(&level0, &MipOptions::default()).unwrap();
+
+    assert_eq!(chain.len(), 3);
+    assert_eq!(chain[2][(0, 0)], Vector4::new(10, 20, 30, 255));
+}
+
+#[test]
+fn test_invalid_filters_rejected() {
+    assert!(MipFilter::lanczos(0).is_err());
+    assert!(MipFilter::kaiser(4.0, 0.0).is_err());
+    assert!(MipFilter::kaiser(-1.0, 3.0).is_err());
+    assert!(MipFilter::kaiser(4.0, 3.0).is_ok());
+
+    let level0 = Array2::from_elem((4, 4), Vector4::new(1.0, 1.0, 1.0, 1.0));
+    let options = MipOptions {
+        filter: MipFilter::Lanczos(0),
+        ..MipOptions::default()
+    };
+    assert!(matches!(
+        generate_mip_chain(&level0, &options),
+        Err(MipError::InvalidFilter(MipFilter::Lanczos(0)))
+    ));
+}
@@ -0,0 +1,90 @@
+use grr_util::procedural::*;
+use nalgebra::Vector4;
+
+#[test]
+fn test_checkerboard() {
+    let a = Vector4::new(0, 0, 0, 255);
+    let b = Vector4::new(255, 255, 255, 255);
+    let c = checkerboard(8, 4, 2, a, b);
+
+    assert_eq!(c.shape(), &[4, 8]);
+    assert_eq!(c[(0, 0)], a);
+    assert_eq!(c[(1, 1)], a);
+    assert_eq!(c[(0, 2)], b);
+    assert_eq!(c[(2, 0)], b);
+    assert_eq!(c[(2, 2)], a);
+}
+
+#[test]
+fn test_gradient_endpoints() {
+    let start = Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let end = Vector4::new(1.0, 0.5, 0.0, 1.0);
+    let g = gradient(5, 2, start, end, GradientDirection::Horizontal);
+
+    assert_eq!(g[(1, 0)], start);
+    assert_eq!(g[(1, 4)], end);
+    assert_eq!(g[(0, 2)], Vector4::new(0.5, 0.25, 0.0, 1.0));
+}
+
+#[test]
+fn test_white_noise_deterministic() {
+    let a = white_noise(16, 16, 7);
+    let b = white_noise(16, 16, 7);
+    let c = white_noise(16, 16, 8);
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert!(a.iter().all(|v| v.x >= 0.0 && v.x < 1.0));
+}
+
+#[test]
+fn test_blue_noise_is_threshold_map() {
+    let size = 16;
+    let noise = blue_noise(size, 3);
+    let mut ranks: Vec<usize> = noise
+        .iter()
+        .map(|v| (v.x * (size * size) as f32).round() as usize)
+        .collect();
+    ranks.sort_unstable();
+
+    assert_eq!(ranks, (0..size * size).collect::<Vec<_>>());
+    assert_eq!(noise, blue_noise(size, 3));
+}
+
+#[test]
+fn test_perlin_2d_range_and_lattice() {
+    let noise = perlin_2d(32, 32, 4, 1, 11);
+
+    assert!(noise.iter().all(|v| v.x >= 0.0 && v.x <= 1.0));
+    // Perlin noise vanishes at lattice points.
+    assert!((noise[(0, 0)].x - 0.5).abs() < 1e-6);
+    assert!((noise[(8, 16)].x - 0.5).abs() < 1e-6);
+    assert_ne!(noise, perlin_2d(32, 32, 4, 1, 12));
+}
+
+#[test]
+fn test_perlin_3d_shape() {
+    let noise = perlin_3d(8, 4, 2, 2, 2, 5);
+
+    assert_eq!(noise.shape(), &[2, 4, 8]);
+    assert!(noise.iter().all(|v| v.x >= 0.0 && v.x <= 1.0));
+    assert_eq!(noise, perlin_3d(8, 4, 2, 2, 2, 5));
+}
+
+#[test]
+fn test_simplex_2d_range() {
+    let noise = simplex_2d(32, 32, 4.0, 3, 1);
+
+    assert!(noise.iter().all(|v| v.x >= 0.0 && v.x <= 1.0));
+    assert!(noise.iter().any(|v| (v.x - 0.5).abs() > 0.05));
+}
+
+#[test]
+fn test_octaves_clamped() {
+    let many = perlin_2d(8, 8, 4, 64, 7);
+    let max = perlin_2d(8, 8, 4, MAX_OCTAVES, 7);
+    assert_eq!(many, max);
+
+    let simplex = simplex_2d(8, 8, 2.0, 64, 7);
+    assert_eq!(simplex, simplex_2d(8, 8, 2.0, MAX_OCTAVES, 7));
+}
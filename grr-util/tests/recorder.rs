@@ -0,0 +1,9 @@
+use grr_util::recorder::frame_file_name;
+
+#[test]
+fn test_frame_file_name() {
+    assert_eq!(frame_file_name("frame_#####.png", 42), "frame_00042.png");
+    assert_eq!(frame_file_name("shot-##.png", 123), "shot-123.png");
+    assert_eq!(frame_file_name("frame.png", 7), "frame7.png");
+    assert_eq!(frame_file_name("frame", 7), "frame7");
+}
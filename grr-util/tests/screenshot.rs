@@ -0,0 +1,58 @@
+use grr_util::screenshot::{
+    DepthScreenshot, FloatScreenshot, ImageFileFormat, ScreenshotError, ToneMapping,
+};
+
+/// Window-space depth of eye-space distance `z` for a perspective
+/// projection with the given planes.
+fn window_depth(z: f32, near: f32, far: f32) -> f32 {
+    let ndc = (far + near - 2.0 * near * far / z) / (far - near);
+    (ndc + 1.0) / 2.0
+}
+
+#[test]
+fn test_linearize() {
+    let (near, far) = (1.0, 10.0);
+    let mut shot = DepthScreenshot {
+        width: 4,
+        height: 1,
+        depth: vec![
+            0.0,
+            window_depth(5.5, near, far),
+            window_depth(3.25, near, far),
+            1.0,
+        ],
+    };
+    shot.linearize(near, far);
+
+    let expected = [0.0, 0.5, 0.25, 1.0];
+    for (d, e) in shot.depth.iter().zip(expected.iter()) {
+        assert!((d - e).abs() < 1e-4, "{} != {}", d, e);
+    }
+}
+
+#[test]
+fn test_mismatched_data() {
+    let path = std::env::temp_dir().join(format!("grr-util-mismatch-{}.png", std::process::id()));
+
+    let float = FloatScreenshot {
+        width: 2,
+        height: 2,
+        pixels: vec![0.0; 3],
+    };
+    let depth = DepthScreenshot {
+        width: 2,
+        height: 2,
+        depth: vec![0.0; 3],
+    };
+    for file_format in &[ImageFileFormat::Png, ImageFileFormat::Png16, ImageFileFormat::Exr] {
+        assert!(matches!(
+            float.save(&path, *file_format, ToneMapping::Clamp),
+            Err(ScreenshotError::Encode(_))
+        ));
+        assert!(matches!(
+            depth.save(&path, *file_format),
+            Err(ScreenshotError::Encode(_))
+        ));
+    }
+    assert!(!path.exists());
+}
@@ -0,0 +1,181 @@
+use grr_util::texture_container::{
+    parse_dds, parse_ktx2, CompressionSupport, ContainerError, TextureContainer, KTX2_IDENTIFIER,
+};
+
+fn push_u32(v: &mut Vec<u8>, x: u32) {
+    v.extend_from_slice(&x.to_le_bytes());
+}
+
+fn push_u64(v: &mut Vec<u8>, x: u64) {
+    v.extend_from_slice(&x.to_le_bytes());
+}
+
+/// Build a KTX2 file with the given header fields and level data,
+/// stored largest level first.
+fn ktx2(vk_format: u32, w: u32, h: u32, layers: u32, faces: u32, levels: &[Vec<u8>]) -> Vec<u8> {
+    let mut v = KTX2_IDENTIFIER.to_vec();
+    for x in &[vk_format, 1, w, h, 0, layers, faces, levels.len() as u32, 0] {
+        push_u32(&mut v, *x);
+    }
+    // empty dfd, kvd and sgd sections
+    for _ in 0..4 {
+        push_u32(&mut v, 0);
+    }
+    push_u64(&mut v, 0);
+    push_u64(&mut v, 0);
+
+    let mut offset = (80 + 24 * levels.len()) as u64;
+    for l in levels {
+        push_u64(&mut v, offset);
+        push_u64(&mut v, l.len() as u64);
+        push_u64(&mut v, l.len() as u64);
+        offset += l.len() as u64;
+    }
+    for l in levels {
+        v.extend_from_slice(l);
+    }
+    v
+}
+
+/// Build a legacy DDS header with a FourCC pixel format.
+fn dds_fourcc(code: &[u8; 4], w: u32, h: u32, levels: u32, caps2: u32) -> Vec<u8> {
+    let mut v = b"DDS ".to_vec();
+    push_u32(&mut v, 124);
+    push_u32(&mut v, 0x1 | 0x2 | 0x4 | 0x1000 | 0x20000);
+    push_u32(&mut v, h);
+    push_u32(&mut v, w);
+    push_u32(&mut v, 0);
+    push_u32(&mut v, 0);
+    push_u32(&mut v, levels);
+    v.extend_from_slice(&[0; 44]);
+    push_u32(&mut v, 32);
+    push_u32(&mut v, 0x4);
+    v.extend_from_slice(code);
+    v.extend_from_slice(&[0; 20]);
+    push_u32(&mut v, 0x1000);
+    push_u32(&mut v, caps2);
+    v.extend_from_slice(&[0; 12]);
+    assert_eq!(v.len(), 128);
+    v
+}
+
+#[test]
+fn test_ktx2_rgba8_mips() {
+    let bytes = ktx2(37, 4, 2, 0, 1, &[vec![1; 32], vec![2; 8], vec![3; 4]]);
+    let c = parse_ktx2(&bytes).unwrap();
+
+    assert_eq!(c.format, grr::Format::R8G8B8A8_UNORM);
+    assert_eq!((c.width, c.height, c.depth), (4, 2, 1));
+    assert_eq!((c.num_layers, c.num_faces), (1, 1));
+    assert_eq!(c.levels, vec![vec![1; 32], vec![2; 8], vec![3; 4]]);
+    assert!(!c.generate_mipmaps);
+}
+
+#[test]
+fn test_ktx2_bgra_swizzled() {
+    let bytes = ktx2(44, 1, 1, 0, 1, &[vec![10, 20, 30, 40]]);
+    let c = parse_ktx2(&bytes).unwrap();
+    assert_eq!(c.format, grr::Format::R8G8B8A8_UNORM);
+    assert_eq!(c.levels[0], vec![30, 20, 10, 40]);
+}
+
+#[test]
+fn test_ktx2_cube_array() {
+    let bytes = ktx2(145, 4, 4, 2, 6, &[vec![0; 16 * 12]]);
+    let c = parse_ktx2(&bytes).unwrap();
+    assert_eq!(c.format, grr::Format::BC7_UNORM_BLOCK);
+    assert!(c.is_cube());
+    assert_eq!(c.num_layers, 2);
+    match c.image_type() {
+        grr::ImageType::D2 { layers, .. } => assert_eq!(layers, 12),
+        _ => panic!("expected a 2D array image"),
+    }
+}
+
+#[test]
+fn test_ktx2_unsupported_format() {
+    let bytes = ktx2(1, 4, 4, 0, 1, &[vec![0; 16]]);
+    match parse_ktx2(&bytes) {
+        Err(ContainerError::UnsupportedFormat(_)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
+
+#[test]
+fn test_ktx2_truncated() {
+    let mut bytes = ktx2(37, 4, 4, 0, 1, &[vec![0; 64]]);
+    bytes.truncate(bytes.len() - 1);
+    assert!(matches!(parse_ktx2(&bytes), Err(ContainerError::Malformed)));
+}
+
+#[test]
+fn test_ktx2_level_length_mismatch() {
+    // A 4x4 RGBA8 level needs 64 bytes.
+    let bytes = ktx2(37, 4, 4, 0, 1, &[vec![0; 32]]);
+    assert!(matches!(parse_ktx2(&bytes), Err(ContainerError::Malformed)));
+}
+
+#[test]
+fn test_ktx2_too_many_levels() {
+    let levels = vec![vec![0; 4]; 2];
+    let bytes = ktx2(37, 1, 1, 0, 1, &levels);
+    assert!(matches!(parse_ktx2(&bytes), Err(ContainerError::Malformed)));
+}
+
+#[test]
+fn test_dds_bad_mip_count() {
+    let mut bytes = dds_fourcc(b"DXT1", 8, 8, u32::MAX, 0);
+    bytes.extend(std::iter::repeat(0).take(64));
+    assert!(matches!(parse_dds(&bytes), Err(ContainerError::Malformed)));
+}
+
+#[test]
+fn test_dds_dxt1_mips() {
+    // 8x8 DXT1: 4 blocks, then 1 block for each of 4x4, 2x2, 1x1
+    let mut bytes = dds_fourcc(b"DXT1", 8, 8, 4, 0);
+    bytes.extend(std::iter::repeat(7).take(32 + 8 + 8 + 8));
+    let c = parse_dds(&bytes).unwrap();
+
+    assert_eq!(c.format, grr::Format::BC1_RGBA_UNORM_BLOCK);
+    assert_eq!(c.levels.len(), 4);
+    assert_eq!(c.levels[0].len(), 32);
+    assert_eq!(c.levels[3].len(), 8);
+}
+
+#[test]
+fn test_dds_cube_regroups_levels() {
+    // 4x4 DXT5 cube with 2 levels; each face holds [face; 16], [face + 100; 16]
+    let mut bytes = dds_fourcc(b"DXT5", 4, 4, 2, 0x200 | 0xfe00);
+    for face in 0..6u8 {
+        bytes.extend(std::iter::repeat(face).take(16));
+        bytes.extend(std::iter::repeat(face + 100).take(16));
+    }
+    let c = TextureContainer::parse(&bytes).unwrap();
+
+    assert_eq!(c.num_faces, 6);
+    assert_eq!(c.levels[0].len(), 6 * 16);
+    assert_eq!(c.levels[0][16], 1);
+    assert_eq!(c.levels[1][0], 100);
+    assert_eq!(c.levels[1][5 * 16], 105);
+}
+
+#[test]
+fn test_compression_support() {
+    let bytes = ktx2(147, 4, 4, 0, 1, &[vec![0; 8]]);
+    let c = parse_ktx2(&bytes).unwrap();
+    assert!(c.check_support(CompressionSupport::default()).is_ok());
+    assert!(c
+        .check_support(CompressionSupport {
+            bc: true,
+            etc2: false
+        })
+        .is_err());
+}
+
+#[test]
+fn test_unknown_container() {
+    assert!(matches!(
+        TextureContainer::parse(b"\x89PNG\r\n\x1a\n"),
+        Err(ContainerError::UnknownContainer)
+    ));
+}
@@ -0,0 +1,40 @@
+use grr_util::tiled_screenshot::*;
+use nalgebra::{Matrix4, Point3, Vector4};
+
+fn region(x: i32, y: i32, w: i32, h: i32) -> grr::Region {
+    grr::Region { x, y, w, h }
+}
+
+#[test]
+fn test_tile_regions_cover_image() {
+    let tiles = tile_regions(10, 5, 4, 4);
+
+    assert_eq!(tiles.len(), 6);
+    assert_eq!((tiles[0].x, tiles[0].y, tiles[0].w, tiles[0].h), (0, 0, 4, 4));
+    assert_eq!((tiles[2].x, tiles[2].w), (8, 2));
+    assert_eq!((tiles[5].y, tiles[5].h), (4, 1));
+
+    let area: i32 = tiles.iter().map(|t| t.w * t.h).sum();
+    assert_eq!(area, 50);
+}
+
+#[test]
+fn test_tile_projection_maps_tile_to_viewport() {
+    let projection = Matrix4::new_perspective(2.0, 1.0, 0.1, 100.0);
+    let (width, height) = (800, 400);
+    let tile = region(400, 100, 200, 100);
+    let tile_proj = tile_projection(&projection, width, height, tile);
+
+    let to_pixel = |m: &Matrix4<f32>, p: &Point3<f32>, w: f32, h: f32| {
+        let c = m * Vector4::new(p.x, p.y, p.z, 1.0);
+        ((c.x / c.w * 0.5 + 0.5) * w, (c.y / c.w * 0.5 + 0.5) * h, c.z / c.w)
+    };
+
+    for p in &[Point3::new(0.3, 0.2, -2.0), Point3::new(1.0, -0.5, -5.0)] {
+        let (fx, fy, fz) = to_pixel(&projection, p, width as f32, height as f32);
+        let (tx, ty, tz) = to_pixel(&tile_proj, p, tile.w as f32, tile.h as f32);
+        assert!((fx - tile.x as f32 - tx).abs() < 1e-2);
+        assert!((fy - tile.y as f32 - ty).abs() < 1e-2);
+        assert!((fz - tz).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,93 @@
+use grr_util::timing::*;
+use std::time::Duration;
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+#[test]
+fn test_fixed_steps_and_alpha() {
+    let mut ts = FixedTimestep::with_clock(ms(10), ManualClock::default()).unwrap();
+
+    ts.clock_mut().advance(ms(25));
+    let f = ts.advance();
+    assert_eq!(f.steps, 2);
+    assert!((f.alpha - 0.5).abs() < 1e-4);
+
+    ts.clock_mut().advance(ms(5));
+    let f = ts.advance();
+    assert_eq!(f.steps, 1);
+    assert!(f.alpha.abs() < 1e-4);
+}
+
+#[test]
+fn test_max_steps_clamps_backlog() {
+    let mut ts = FixedTimestep::with_clock(ms(10), ManualClock::default()).unwrap();
+    ts.max_steps = 4;
+
+    ts.clock_mut().advance(ms(1000));
+    assert_eq!(ts.advance().steps, 4);
+
+    // The backlog is dropped rather than carried into later frames.
+    ts.clock_mut().advance(ms(1));
+    assert_eq!(ts.advance().steps, 0);
+}
+
+#[test]
+fn test_pause_and_single_step() {
+    let mut ts = FixedTimestep::with_clock(ms(10), ManualClock::default()).unwrap();
+    ts.set_paused(true);
+
+    ts.clock_mut().advance(ms(100));
+    assert_eq!(ts.advance().steps, 0);
+
+    ts.single_step();
+    ts.clock_mut().advance(ms(100));
+    assert_eq!(ts.advance().steps, 1);
+    assert_eq!(ts.advance().steps, 0);
+
+    // Time spent paused is not made up after resuming.
+    ts.set_paused(false);
+    ts.clock_mut().advance(ms(10));
+    assert_eq!(ts.advance().steps, 1);
+}
+
+#[test]
+fn test_frame_limiter() {
+    let mut limiter = FrameLimiter::with_clock(Some(50.0), ManualClock::default()).unwrap();
+    limiter.clock_mut().advance(ms(5));
+    assert_eq!(limiter.remaining(), ms(15));
+
+    limiter.clock_mut().advance(ms(30));
+    assert_eq!(limiter.remaining(), ms(0));
+
+    let unlimited = FrameLimiter::with_clock(None, ManualClock::default()).unwrap();
+    assert_eq!(unlimited.remaining(), ms(0));
+}
+
+#[test]
+fn test_zero_step_rejected() {
+    assert!(matches!(
+        FixedTimestep::with_clock(ms(0), ManualClock::default()),
+        Err(TimingError::ZeroTimestep)
+    ));
+}
+
+#[test]
+fn test_invalid_frame_rates_rejected() {
+    for &fps in &[0.0, -1.0, std::f32::NAN, 1e-30] {
+        assert!(matches!(
+            FrameLimiter::with_clock(Some(fps), ManualClock::default()),
+            Err(TimingError::InvalidFrameRate(_))
+        ));
+    }
+}
+
+#[test]
+fn test_long_step_does_not_overflow() {
+    let mut ts =
+        FixedTimestep::with_clock(Duration::from_secs(u64::MAX / 2), ManualClock::default())
+            .unwrap();
+    ts.clock_mut().advance(ms(10));
+    assert_eq!(ts.advance().steps, 0);
+}
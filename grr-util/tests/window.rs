@@ -0,0 +1,23 @@
+use grr_util::window::Error;
+use grr_util::{VideoModeRequest, WindowMode};
+
+#[test]
+fn test_no_monitors() {
+    assert!(matches!(WindowMode::Windowed.fullscreen(vec![], None), Ok(None)));
+    assert!(matches!(
+        WindowMode::Borderless { monitor: Some(0) }.fullscreen(vec![], None),
+        Err(Error::NoSuchMonitor(0))
+    ));
+    assert!(matches!(
+        WindowMode::Borderless { monitor: None }.fullscreen(vec![], None),
+        Ok(Some(glutin::window::Fullscreen::Borderless(None)))
+    ));
+    assert!(matches!(
+        WindowMode::Exclusive {
+            monitor: None,
+            video_mode: VideoModeRequest::default(),
+        }
+        .fullscreen(vec![], None),
+        Err(Error::NoSuchMonitor(0))
+    ));
+}